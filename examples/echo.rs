@@ -1,5 +1,5 @@
 extern crate xmpp;
-use xmpp::stanzas::{Presence, PresenceType, Stanza};
+use xmpp::stanzas::{Message, MessageType, Presence, PresenceType, Stanza};
 use xmpp::XmppStream;
 
 fn main() {
@@ -15,18 +15,21 @@ fn main() {
         let (opt_response, send_presence) = match stream.handle() {
             xmpp::Event::StreamClosed => break,
             xmpp::Event::Message(msg) => {
-                let mut response = msg.clone();
-                let to = response.from().map(|x| x.into());
-                response.set_to(to);
-                response.set_from(None);
+                let body = match msg.body(None) {
+                    Some(body) => body,
+                    None => continue,
+                };
+                let mut response = Message::new(MessageType::Chat, "".to_owned());
+                response.set_to(msg.from().map(|x| x.into()));
+                response.set_body(None, Some(body));
+                response.set_thread(msg.thread());
                 (Some(response), false)
             }
             xmpp::Event::Bound(_jid) => (None, true),
             _ => continue,
         };
-        match opt_response {
-            Some(response) => stream.send(response).unwrap(),
-            None => (),
+        if let Some(response) = opt_response {
+            stream.send(response).unwrap();
         }
         if send_presence {
             stream
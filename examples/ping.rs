@@ -1,12 +1,13 @@
-extern crate xml;
 extern crate xmpp;
-use xmpp::stanzas::{Iq, IqType, Stanza};
+use std::time::Duration;
 use xmpp::XmppStream;
 
-const NS_PING: &str = "urn:xmpp:ping";
-
 fn main() {
     let mut stream = XmppStream::new("alice", "localhost", "test");
+    // Pings are answered automatically, additionally check the connection
+    // is still alive after a minute without traffic
+    stream.set_ping_interval(Some(Duration::from_secs(60)));
+    stream.set_ping_timeout(Duration::from_secs(30));
     match stream.connect() {
         Ok(_) => (),
         Err(e) => {
@@ -17,26 +18,6 @@ fn main() {
     loop {
         match stream.handle() {
             xmpp::Event::StreamClosed => break,
-            xmpp::Event::IqRequest(mut iq) => {
-                if let Some(IqType::Get) = iq.stanza_type() {
-                    if iq.get_child("ping", Some(NS_PING)).is_some() {
-                        let id = if let Some(id) = iq.id() {
-                            id.into()
-                        } else {
-                            continue;
-                        };
-                        let to = iq.from().map(|x| x.into());
-                        let mut response = Iq::new(IqType::Result, id);
-                        response.set_to(to);
-                        response.tag(xml::Element::new(
-                            "pong".into(),
-                            Some(NS_PING.into()),
-                            vec![],
-                        ));
-                        iq.respond(&response);
-                    }
-                }
-            }
             _ => continue,
         }
     }
@@ -0,0 +1,595 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! Ad-Hoc Commands (XEP-0050)
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::data_forms::DataForm;
+use crate::disco::{DiscoInfo, DiscoItem, DiscoItems, Identity};
+use crate::jid;
+use crate::ns;
+use crate::stanzas::{DefinedCondition, ErrorType, Iq, IqType, Payload, Stanza, StanzaError};
+use crate::{iq_error, malformed, Event, PendingIqKind, XmppHandler, XmppStream};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Starts the command, or proceeds with the default action
+    Execute,
+    Cancel,
+    Prev,
+    Next,
+    Complete,
+}
+
+impl Action {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            Action::Execute => "execute",
+            Action::Cancel => "cancel",
+            Action::Prev => "prev",
+            Action::Next => "next",
+            Action::Complete => "complete",
+        }
+    }
+
+    fn from_str(action: &str) -> Option<Action> {
+        match action {
+            "execute" => Some(Action::Execute),
+            "cancel" => Some(Action::Cancel),
+            "prev" => Some(Action::Prev),
+            "next" => Some(Action::Next),
+            "complete" => Some(Action::Complete),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// The command awaits further input
+    Executing,
+    Completed,
+    Canceled,
+}
+
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            Status::Executing => "executing",
+            Status::Completed => "completed",
+            Status::Canceled => "canceled",
+        }
+    }
+
+    fn from_str(status: &str) -> Option<Status> {
+        match status {
+            "executing" => Some(Status::Executing),
+            "completed" => Some(Status::Completed),
+            "canceled" => Some(Status::Canceled),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NoteType {
+    Info,
+    Warn,
+    Error,
+}
+
+impl NoteType {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            NoteType::Info => "info",
+            NoteType::Warn => "warn",
+            NoteType::Error => "error",
+        }
+    }
+
+    fn from_str(ty: &str) -> Option<NoteType> {
+        match ty {
+            "info" => Some(NoteType::Info),
+            "warn" => Some(NoteType::Warn),
+            "error" => Some(NoteType::Error),
+            _ => None,
+        }
+    }
+}
+
+/// A message about the outcome of a command stage
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Note {
+    pub ty: NoteType,
+    pub text: String,
+}
+
+impl Note {
+    pub fn new(ty: NoteType, text: &str) -> Note {
+        Note {
+            ty,
+            text: text.into(),
+        }
+    }
+}
+
+/// The `<command xmlns='http://jabber.org/protocol/commands'/>` payload
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Command {
+    pub node: String,
+    pub session_id: Option<String>,
+    /// The requested action, only set in requests
+    pub action: Option<Action>,
+    /// Only set in responses
+    pub status: Option<Status>,
+    /// The actions allowed in the next stage, besides cancelling
+    pub actions: Vec<Action>,
+    /// The action taken by `Action::Execute` in the next stage
+    pub default_action: Option<Action>,
+    pub notes: Vec<Note>,
+    pub form: Option<DataForm>,
+}
+
+impl Command {
+    pub fn new(node: &str) -> Command {
+        Command {
+            node: node.into(),
+            session_id: None,
+            action: None,
+            status: None,
+            actions: vec![],
+            default_action: None,
+            notes: vec![],
+            form: None,
+        }
+    }
+}
+
+impl Payload for Command {
+    const NAME: &'static str = "command";
+    const NS: &'static str = ns::COMMANDS;
+
+    fn try_from(elem: &xml::Element) -> Result<Command, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a command");
+        }
+        let attr = |name| elem.get_attribute(name, None);
+        let action = match attr("action") {
+            Some(action) => Some(Action::from_str(action).ok_or("Invalid command action")?),
+            None => None,
+        };
+        let status = match attr("status") {
+            Some(status) => Some(Status::from_str(status).ok_or("Invalid command status")?),
+            None => None,
+        };
+        let (actions, default_action) = match elem.get_child("actions", Some(Self::NS)) {
+            Some(actions) => {
+                let default = match actions.get_attribute("execute", None) {
+                    Some(action) => Some(Action::from_str(action).ok_or("Invalid default action")?),
+                    None => None,
+                };
+                let allowed = actions
+                    .children
+                    .iter()
+                    .filter_map(|child| match *child {
+                        xml::Xml::ElementNode(ref child) => Action::from_str(&child.name),
+                        _ => None,
+                    })
+                    .collect();
+                (allowed, default)
+            }
+            None => (vec![], None),
+        };
+        let notes = elem
+            .get_children("note", Some(Self::NS))
+            .map(|note| Note {
+                ty: note
+                    .get_attribute("type", None)
+                    .and_then(NoteType::from_str)
+                    .unwrap_or(NoteType::Info),
+                text: note.content_str(),
+            })
+            .collect();
+        Ok(Command {
+            node: attr("node").ok_or("Command without node")?.into(),
+            session_id: attr("sessionid").map(|x| x.into()),
+            action,
+            status,
+            actions,
+            default_action,
+            notes,
+            form: match elem.get_child("x", Some(ns::DATA_FORMS)) {
+                Some(form) => Some(<DataForm as Payload>::try_from(form)?),
+                None => None,
+            },
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut attrs = vec![("node".into(), None, self.node.clone())];
+        if let Some(ref session_id) = self.session_id {
+            attrs.push(("sessionid".into(), None, session_id.clone()));
+        }
+        if let Some(action) = self.action {
+            attrs.push(("action".into(), None, action.as_str().into()));
+        }
+        if let Some(status) = self.status {
+            attrs.push(("status".into(), None, status.as_str().into()));
+        }
+        let mut command = xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), attrs);
+        if !self.actions.is_empty() || self.default_action.is_some() {
+            let attrs = self
+                .default_action
+                .map(|action| ("execute".into(), None, action.as_str().into()));
+            let actions = command.tag(xml::Element::new(
+                "actions".into(),
+                Some(Self::NS.into()),
+                attrs,
+            ));
+            for action in &self.actions {
+                actions.tag(xml::Element::new(
+                    action.as_str().into(),
+                    Some(Self::NS.into()),
+                    vec![],
+                ));
+            }
+        }
+        for note in &self.notes {
+            command
+                .tag(xml::Element::new(
+                    "note".into(),
+                    Some(Self::NS.into()),
+                    vec![("type".into(), None, note.ty.as_str().into())],
+                ))
+                .text(note.text.clone());
+        }
+        if let Some(ref form) = self.form {
+            command.tag(form.to_element());
+        }
+        command
+    }
+}
+
+/// One stage of a command executed by another entity
+pub struct CommandRequest<'a> {
+    /// The full JID of the requester, or our bare JID for requests from our own server
+    pub from: &'a str,
+    /// Identifies the execution across stages, e.g. to keep state between them
+    pub session_id: &'a str,
+    /// `Action::Execute` when the command is started
+    pub action: Action,
+    /// The submitted form, if any
+    pub form: Option<DataForm>,
+}
+
+/// The result of one stage of a command
+#[derive(Clone, Debug)]
+pub struct CommandResponse {
+    pub status: Status,
+    /// The actions allowed in the next stage, besides cancelling
+    pub actions: Vec<Action>,
+    pub default_action: Option<Action>,
+    pub notes: Vec<Note>,
+    pub form: Option<DataForm>,
+}
+
+impl CommandResponse {
+    /// Finishes the command, optionally returning a form of type `result`
+    pub fn completed(form: Option<DataForm>) -> CommandResponse {
+        CommandResponse {
+            status: Status::Completed,
+            actions: vec![],
+            default_action: None,
+            notes: vec![],
+            form,
+        }
+    }
+
+    /// Asks for `form` to be filled out in another stage.
+    ///
+    /// `actions` are the allowed actions, the first one is the default.
+    pub fn executing(form: DataForm, actions: Vec<Action>) -> CommandResponse {
+        CommandResponse {
+            status: Status::Executing,
+            default_action: actions.first().cloned(),
+            actions,
+            notes: vec![],
+            form: Some(form),
+        }
+    }
+
+    pub fn with_note(mut self, ty: NoteType, text: &str) -> CommandResponse {
+        self.notes.push(Note::new(ty, text));
+        self
+    }
+}
+
+/// A command we offer to other entities
+pub trait CommandHandler {
+    /// Handles one stage of the command.
+    ///
+    /// Is also called with `Action::Cancel` when the requester cancels the
+    /// command, the response to that is ignored.
+    #[allow(clippy::result_large_err)]
+    fn handle(&mut self, request: CommandRequest) -> Result<CommandResponse, StanzaError>;
+}
+
+impl<F> CommandHandler for F
+where
+    F: FnMut(CommandRequest) -> Result<CommandResponse, StanzaError>,
+{
+    fn handle(&mut self, request: CommandRequest) -> Result<CommandResponse, StanzaError> {
+        self(request)
+    }
+}
+
+/// Decides who may list and execute a command, given the requester's full JID
+pub type CommandAccess = Box<dyn Fn(&str) -> bool>;
+
+/// How long a multi-stage command may be idle before its session is dropped
+const SESSION_TIMEOUT: Duration = Duration::from_secs(600);
+
+pub(crate) struct RegisteredCommand {
+    name: String,
+    allowed: Option<CommandAccess>,
+    handler: Box<dyn CommandHandler>,
+}
+
+/// A command executed by another entity, spanning several stages
+pub(crate) struct CommandSession {
+    node: String,
+    from: String,
+    last_active: Instant,
+}
+
+impl XmppStream {
+    /// Requests the commands `jid` offers.
+    ///
+    /// Returns the id of the request, the commands are reported by `Event::DiscoItems`,
+    /// with the command's node and name in each item.
+    pub fn list_commands(&mut self, jid: &str) -> io::Result<String> {
+        self.query_disco_items(jid, Some(ns::COMMANDS))
+    }
+
+    /// Starts the command `node` of `jid`.
+    ///
+    /// Returns the id of the request, the response is reported by `Event::Command`.
+    pub fn execute_command(&mut self, jid: &str, node: &str) -> io::Result<String> {
+        self.send_command(jid, Command::new(node))
+    }
+
+    /// Proceeds with a multi-stage command, using the node and session id of
+    /// the previous response.
+    ///
+    /// `form` is usually the filled out form of the previous response, see
+    /// `DataForm::to_submit()`.
+    /// Returns the id of the request, the response is reported by `Event::Command`.
+    pub fn continue_command(
+        &mut self,
+        jid: &str,
+        previous: &Command,
+        action: Action,
+        form: Option<DataForm>,
+    ) -> io::Result<String> {
+        let mut command = Command::new(&previous.node);
+        command.session_id = previous.session_id.clone();
+        command.action = Some(action);
+        command.form = form;
+        self.send_command(jid, command)
+    }
+
+    /// Offers the command `node` to other entities, replacing any command with the same node.
+    ///
+    /// `allowed` decides who may list and execute the command, `None` restricts
+    /// it to our own account. Others are answered with `forbidden`.
+    pub fn register_command(
+        &mut self,
+        node: &str,
+        name: &str,
+        allowed: Option<CommandAccess>,
+        handler: Box<dyn CommandHandler>,
+    ) {
+        self.add_disco_feature(ns::COMMANDS);
+        self.handler.commands.insert(
+            node.into(),
+            RegisteredCommand {
+                name: name.into(),
+                allowed,
+                handler,
+            },
+        );
+    }
+
+    pub fn unregister_command(&mut self, node: &str) {
+        self.handler.commands.remove(node);
+        self.handler
+            .command_sessions
+            .retain(|_, session| session.node != node);
+        if self.handler.commands.is_empty() {
+            self.remove_disco_feature(ns::COMMANDS);
+        }
+    }
+
+    fn send_command(&mut self, jid: &str, command: Command) -> io::Result<String> {
+        let mut iq = Iq::new(IqType::Set, String::new());
+        iq.set_to(Some(jid.into()));
+        iq.add_payload(command);
+        self.handler.send_iq(iq, PendingIqKind::Command)
+    }
+}
+
+impl XmppHandler {
+    pub(crate) fn handle_command_result(&mut self, jid: String, iq: Iq) {
+        let result = match iq.stanza_type() {
+            Some(IqType::Error) => Err(iq_error(&iq)),
+            _ => iq.get_payload::<Command>().ok_or_else(malformed),
+        };
+        let id = iq.id().unwrap().into();
+        self.events.push_back(Event::Command { id, jid, result });
+    }
+
+    pub(crate) fn handle_command_request(&mut self, iq: Iq) {
+        let reply = match self.execute_command(&iq) {
+            Ok(command) => {
+                let mut reply = iq.result_reply();
+                reply.add_payload(command);
+                reply
+            }
+            Err(error) => iq.error_reply(error.ty, error.condition, error.text),
+        };
+        let _ = self.send(reply);
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn execute_command(&mut self, iq: &Iq) -> Result<Command, StanzaError> {
+        let bad_request = || StanzaError::new(ErrorType::Modify, DefinedCondition::BadRequest);
+        let request = iq.get_payload::<Command>().ok_or_else(bad_request)?;
+        let from = self.command_requester(iq);
+        match self.commands.get(&request.node) {
+            Some(command) if self.command_allowed(command, &from) => (),
+            Some(_) => {
+                return Err(StanzaError::new(
+                    ErrorType::Auth,
+                    DefinedCondition::Forbidden,
+                ))
+            }
+            None => {
+                return Err(StanzaError::new(
+                    ErrorType::Cancel,
+                    DefinedCondition::ItemNotFound,
+                ))
+            }
+        }
+
+        let now = Instant::now();
+        self.command_sessions
+            .retain(|_, session| now.duration_since(session.last_active) < SESSION_TIMEOUT);
+        let action = request.action.unwrap_or(Action::Execute);
+        let session_id = match request.session_id {
+            Some(session_id) => {
+                match self.command_sessions.get_mut(&session_id) {
+                    Some(session) if session.node == request.node && session.from == from => {
+                        session.last_active = now;
+                    }
+                    _ => return Err(bad_request()),
+                }
+                session_id
+            }
+            None if action == Action::Execute => {
+                let session_id = self.next_id();
+                self.command_sessions.insert(
+                    session_id.clone(),
+                    CommandSession {
+                        node: request.node.clone(),
+                        from: from.clone(),
+                        last_active: now,
+                    },
+                );
+                session_id
+            }
+            None => return Err(bad_request()),
+        };
+
+        let handler = &mut self.commands.get_mut(&request.node).unwrap().handler;
+        let result = handler.handle(CommandRequest {
+            from: &from,
+            session_id: &session_id,
+            action,
+            form: request.form,
+        });
+        let response = if action == Action::Cancel {
+            Ok(CommandResponse {
+                status: Status::Canceled,
+                ..CommandResponse::completed(None)
+            })
+        } else {
+            result
+        };
+        if !matches!(
+            response,
+            Ok(CommandResponse {
+                status: Status::Executing,
+                ..
+            })
+        ) {
+            self.command_sessions.remove(&session_id);
+        }
+        let response = response?;
+        Ok(Command {
+            node: request.node,
+            session_id: Some(session_id),
+            action: None,
+            status: Some(response.status),
+            actions: response.actions,
+            default_action: response.default_action,
+            notes: response.notes,
+            form: response.form,
+        })
+    }
+
+    /// The requester of `iq`, requests without 'from' come from our own account
+    pub(crate) fn command_requester(&self, iq: &Iq) -> String {
+        match iq.from() {
+            Some(from) => from.into(),
+            None => self.jid.as_deref().map(jid::bare).unwrap_or("").into(),
+        }
+    }
+
+    /// Whether `from` may list and execute `command`
+    fn command_allowed(&self, command: &RegisteredCommand, from: &str) -> bool {
+        match command.allowed {
+            Some(ref allowed) => allowed(from),
+            None => self.jid.as_deref().map(jid::bare) == Some(jid::bare(from)),
+        }
+    }
+
+    /// The commands `from` may execute, answering disco#items for the commands node
+    pub(crate) fn command_items(&self, from: &str) -> DiscoItems {
+        let jid = self.jid.clone().unwrap_or_default();
+        DiscoItems {
+            node: Some(ns::COMMANDS.into()),
+            items: self
+                .commands
+                .iter()
+                .filter(|(_, command)| self.command_allowed(command, from))
+                .map(|(node, command)| DiscoItem {
+                    jid: jid.clone(),
+                    node: Some(node.clone()),
+                    name: Some(command.name.clone()),
+                })
+                .collect(),
+            set: None,
+        }
+    }
+
+    /// The disco#info of the commands node or one of the commands `from` may execute,
+    /// if `node` is one
+    pub(crate) fn command_info(&self, node: &str, from: &str) -> Option<DiscoInfo> {
+        if node == ns::COMMANDS {
+            return Some(DiscoInfo {
+                node: Some(node.into()),
+                identities: vec![Identity::new("automation", "command-list", None)],
+                ..DiscoInfo::default()
+            });
+        }
+        let command = self
+            .commands
+            .get(node)
+            .filter(|command| self.command_allowed(command, from))?;
+        Some(DiscoInfo {
+            node: Some(node.into()),
+            identities: vec![Identity::new(
+                "automation",
+                "command-node",
+                Some(&command.name),
+            )],
+            features: vec![ns::COMMANDS.into(), ns::DATA_FORMS.into()],
+            extensions: vec![],
+        })
+    }
+}
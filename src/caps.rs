@@ -0,0 +1,557 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! Entity Capabilities (XEP-0115 and XEP-0390)
+
+use std::collections::HashMap;
+
+use openssl::hash::{hash, MessageDigest};
+
+use crate::data_forms::DataForm;
+use crate::disco::{DiscoInfo, Identity};
+use crate::ns;
+use crate::stanzas::{Iq, IqType, Payload, Presence, PresenceType, Stanza};
+use crate::{PendingIqKind, XmppHandler, XmppStream};
+
+/// The node we advertise in XEP-0115 capabilities by default
+pub const DEFAULT_NODE: &str = "https://github.com/Florob/rust-xmpp";
+
+/// The algorithm used for our own XEP-0390 capabilities
+const CAPS2_ALGO: &str = "sha-256";
+
+/// Legacy capabilities, `<c xmlns='http://jabber.org/protocol/caps'/>` (XEP-0115)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Caps {
+    pub hash: String,
+    pub node: String,
+    pub ver: String,
+}
+
+impl Payload for Caps {
+    const NAME: &'static str = "c";
+    const NS: &'static str = ns::CAPS;
+
+    fn try_from(elem: &xml::Element) -> Result<Caps, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a caps element");
+        }
+        let attr = |name| elem.get_attribute(name, None).map(|x| x.to_string());
+        Ok(Caps {
+            // Pre-1.5 capabilities without hash can not be verified
+            hash: attr("hash").ok_or("Legacy caps without hash")?,
+            node: attr("node").ok_or("Caps without node")?,
+            ver: attr("ver").ok_or("Caps without ver")?,
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        xml::Element::new(
+            Self::NAME.into(),
+            Some(Self::NS.into()),
+            vec![
+                ("hash".into(), None, self.hash.clone()),
+                ("node".into(), None, self.node.clone()),
+                ("ver".into(), None, self.ver.clone()),
+            ],
+        )
+    }
+}
+
+/// A hash value as used by XEP-0390 (XEP-0300)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hash {
+    pub algo: String,
+    /// The base64 encoded hash value
+    pub value: String,
+}
+
+/// Capabilities, `<c xmlns='urn:xmpp:caps'/>` (XEP-0390)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Caps2 {
+    pub hashes: Vec<Hash>,
+}
+
+impl Payload for Caps2 {
+    const NAME: &'static str = "c";
+    const NS: &'static str = ns::CAPS2;
+
+    fn try_from(elem: &xml::Element) -> Result<Caps2, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a caps element");
+        }
+        let hashes = elem
+            .get_children("hash", Some(ns::HASHES))
+            .map(|hash| {
+                let algo = hash
+                    .get_attribute("algo", None)
+                    .ok_or("Hash without algo")?;
+                Ok(Hash {
+                    algo: algo.into(),
+                    value: hash.content_str(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Caps2 { hashes })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut c = xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), vec![]);
+        for hash in &self.hashes {
+            c.tag(xml::Element::new(
+                "hash".into(),
+                Some(ns::HASHES.into()),
+                vec![("algo".into(), None, hash.algo.clone())],
+            ))
+            .text(hash.value.clone());
+        }
+        c
+    }
+}
+
+fn digest(algo: &str) -> Option<MessageDigest> {
+    match algo {
+        "sha-1" => Some(MessageDigest::sha1()),
+        "sha-256" => Some(MessageDigest::sha256()),
+        "sha-512" => Some(MessageDigest::sha512()),
+        "sha3-256" => Some(MessageDigest::sha3_256()),
+        "sha3-512" => Some(MessageDigest::sha3_512()),
+        _ => None,
+    }
+}
+
+fn base64_digest(algo: &str, data: &[u8]) -> Option<String> {
+    let digest = digest(algo)?;
+    hash(digest, data).ok().map(base64::encode)
+}
+
+/// A form field as (var, values)
+type Field = (String, Vec<String>);
+
+fn form_fields(form: &DataForm) -> Vec<Field> {
+    form.fields
+        .iter()
+        .map(|field| (field.var.clone().unwrap_or_default(), field.values.clone()))
+        .collect()
+}
+
+/// Whether `info` contains duplicates, which make its hash ambiguous
+fn has_duplicates(info: &DiscoInfo) -> bool {
+    let mut identities: Vec<&Identity> = info.identities.iter().collect();
+    identities.sort();
+    identities.dedup();
+    let mut features: Vec<&String> = info.features.iter().collect();
+    features.sort();
+    features.dedup();
+    let form_types: Vec<&str> = info
+        .extensions
+        .iter()
+        .filter_map(|form| form.form_type())
+        .collect();
+    let mut unique_form_types = form_types.clone();
+    unique_form_types.sort();
+    unique_form_types.dedup();
+    identities.len() != info.identities.len()
+        || features.len() != info.features.len()
+        || unique_form_types.len() != form_types.len()
+}
+
+/// The XEP-0115 verification string of `info`, hashed with `algo`
+pub fn caps_hash(info: &DiscoInfo, algo: &str) -> Option<String> {
+    let mut s = String::new();
+
+    let mut identities: Vec<&Identity> = info.identities.iter().collect();
+    identities.sort_by(|a, b| (&a.category, &a.ty, &a.lang).cmp(&(&b.category, &b.ty, &b.lang)));
+    for identity in identities {
+        s.push_str(&identity.category);
+        s.push('/');
+        s.push_str(&identity.ty);
+        s.push('/');
+        s.push_str(identity.lang.as_deref().unwrap_or(""));
+        s.push('/');
+        s.push_str(identity.name.as_deref().unwrap_or(""));
+        s.push('<');
+    }
+
+    let mut features: Vec<&String> = info.features.iter().collect();
+    features.sort();
+    for feature in features {
+        s.push_str(feature);
+        s.push('<');
+    }
+
+    // Forms without FORM_TYPE are ignored
+    let mut forms: Vec<(String, Vec<Field>)> = info
+        .extensions
+        .iter()
+        .filter_map(|form| {
+            let mut fields = form_fields(form);
+            let idx = fields.iter().position(|(var, _)| var == "FORM_TYPE")?;
+            let form_type = fields.remove(idx).1.into_iter().next().unwrap_or_default();
+            Some((form_type, fields))
+        })
+        .collect();
+    forms.sort();
+    for (form_type, mut fields) in forms {
+        s.push_str(&form_type);
+        s.push('<');
+        fields.sort();
+        for (var, mut values) in fields {
+            s.push_str(&var);
+            s.push('<');
+            values.sort();
+            for value in values {
+                s.push_str(&value);
+                s.push('<');
+            }
+        }
+    }
+
+    base64_digest(algo, s.as_bytes())
+}
+
+/// The XEP-0390 hash of `info`, using `algo`
+pub fn caps2_hash(info: &DiscoInfo, algo: &str) -> Option<String> {
+    fn joined(mut parts: Vec<Vec<u8>>, terminator: u8) -> Vec<u8> {
+        parts.sort();
+        let mut result = parts.concat();
+        result.push(terminator);
+        result
+    }
+    fn unit(s: &str) -> Vec<u8> {
+        let mut result = s.as_bytes().to_vec();
+        result.push(0x1f);
+        result
+    }
+
+    let features = info.features.iter().map(|f| unit(f)).collect();
+
+    let identities = info
+        .identities
+        .iter()
+        .map(|identity| {
+            let mut result = unit(&identity.category);
+            result.extend(unit(&identity.ty));
+            result.extend(unit(identity.lang.as_deref().unwrap_or("")));
+            result.extend(unit(identity.name.as_deref().unwrap_or("")));
+            result.push(0x1e);
+            result
+        })
+        .collect();
+
+    let forms = info
+        .extensions
+        .iter()
+        .map(|form| {
+            let fields = form_fields(form)
+                .into_iter()
+                .map(|(var, values)| {
+                    let mut result = unit(&var);
+                    result.extend(joined(values.iter().map(|v| unit(v)).collect(), 0x1e));
+                    result
+                })
+                .collect();
+            joined(fields, 0x1d)
+        })
+        .collect();
+
+    let mut input = joined(features, 0x1c);
+    input.extend(joined(identities, 0x1c));
+    input.extend(joined(forms, 0x1c));
+    base64_digest(algo, &input)
+}
+
+/// Cache of verified capabilities, keyed by their disco node
+pub trait CapsStore {
+    fn get(&self, node: &str) -> Option<DiscoInfo>;
+    fn insert(&mut self, node: &str, info: DiscoInfo);
+}
+
+/// A `CapsStore` keeping capabilities in memory
+#[derive(Default)]
+pub struct MemoryCapsStore {
+    caps: HashMap<String, DiscoInfo>,
+}
+
+impl MemoryCapsStore {
+    pub fn new() -> MemoryCapsStore {
+        MemoryCapsStore::default()
+    }
+}
+
+impl CapsStore for MemoryCapsStore {
+    fn get(&self, node: &str) -> Option<DiscoInfo> {
+        self.caps.get(node).cloned()
+    }
+
+    fn insert(&mut self, node: &str, info: DiscoInfo) {
+        self.caps.insert(node.into(), info);
+    }
+}
+
+/// A capabilities hash announced by an entity, not yet verified
+pub(crate) struct Announced {
+    /// The disco node the hash refers to
+    node: String,
+    /// `true` for XEP-0390, `false` for XEP-0115
+    caps2: bool,
+    algo: String,
+    value: String,
+}
+
+fn announced_caps(presence: &Presence) -> Option<Announced> {
+    if let Some(caps2) = presence.get_payload::<Caps2>() {
+        let hash = caps2
+            .hashes
+            .into_iter()
+            .find(|hash| hash.algo != "sha-1" && digest(&hash.algo).is_some());
+        if let Some(hash) = hash {
+            return Some(Announced {
+                node: format!("{}#{}.{}", ns::CAPS2, hash.algo, hash.value),
+                caps2: true,
+                algo: hash.algo,
+                value: hash.value,
+            });
+        }
+    }
+
+    let caps = presence.get_payload::<Caps>()?;
+    digest(&caps.hash)?;
+    Some(Announced {
+        node: format!("{}#{}", caps.node, caps.ver),
+        caps2: false,
+        algo: caps.hash,
+        value: caps.ver,
+    })
+}
+
+impl XmppStream {
+    /// Sets the node advertised in our XEP-0115 capabilities
+    pub fn set_caps_node(&mut self, node: &str) {
+        self.handler.caps_node = node.into();
+    }
+
+    /// Replaces the cache of verified capabilities, by default a `MemoryCapsStore`
+    pub fn set_caps_store(&mut self, store: Box<dyn CapsStore>) {
+        self.handler.caps_store = store;
+    }
+
+    /// The verified disco#info of `jid`, based on its announced capabilities
+    pub fn capabilities(&self, jid: &str) -> Option<DiscoInfo> {
+        self.handler
+            .entity_caps
+            .get(jid)
+            .and_then(|node| self.handler.caps_store.get(node))
+    }
+}
+
+impl XmppHandler {
+    /// The disco nodes our current capabilities are available at
+    pub(crate) fn own_caps_nodes(&self) -> (String, String) {
+        let ver = caps_hash(&self.disco_info, "sha-1").unwrap_or_default();
+        let hash = caps2_hash(&self.disco_info, CAPS2_ALGO).unwrap_or_default();
+        (
+            format!("{}#{}", self.caps_node, ver),
+            format!("{}#{}.{}", ns::CAPS2, CAPS2_ALGO, hash),
+        )
+    }
+
+    pub(crate) fn add_caps(&self, presence: &mut Presence) {
+        if let Some(ver) = caps_hash(&self.disco_info, "sha-1") {
+            presence.add_payload(Caps {
+                hash: "sha-1".into(),
+                node: self.caps_node.clone(),
+                ver,
+            });
+        }
+        if let Some(value) = caps2_hash(&self.disco_info, CAPS2_ALGO) {
+            presence.add_payload(Caps2 {
+                hashes: vec![Hash {
+                    algo: CAPS2_ALGO.into(),
+                    value,
+                }],
+            });
+        }
+    }
+
+    /// Looks up the capabilities announced in `presence`, querying them if unknown
+    pub(crate) fn handle_caps(&mut self, presence: &Presence) {
+        let from = match presence.from() {
+            Some(from) => from.to_string(),
+            None => return,
+        };
+        if presence.stanza_type() != Some(PresenceType::Available) {
+            self.entity_caps.remove(&from);
+            return;
+        }
+        let announced = match announced_caps(presence) {
+            Some(announced) => announced,
+            None => {
+                self.entity_caps.remove(&from);
+                return;
+            }
+        };
+
+        if self.caps_store.get(&announced.node).is_some() {
+            self.entity_caps.insert(from, announced.node);
+        } else if let Some(&mut (_, ref mut waiting)) = self.pending_caps.get_mut(&announced.node) {
+            // Only query each hash once, other entities wait for the result
+            waiting.push(from);
+        } else {
+            let mut iq = Iq::new(IqType::Get, String::new());
+            iq.set_to(Some(from.clone()));
+            iq.add_payload(DiscoInfo {
+                node: Some(announced.node.clone()),
+                ..DiscoInfo::default()
+            });
+            let node = announced.node.clone();
+            if self.send_iq(iq, PendingIqKind::Caps(node.clone())).is_ok() {
+                self.pending_caps.insert(node, (announced, vec![from]));
+            }
+        }
+    }
+
+    pub(crate) fn handle_caps_result(&mut self, node: String, iq: Iq) {
+        let (announced, waiting) = match self.pending_caps.remove(&node) {
+            Some(pending) => pending,
+            None => return,
+        };
+        let mut info = match iq.get_payload::<DiscoInfo>() {
+            Some(info) if matches!(iq.stanza_type(), Some(IqType::Result)) => info,
+            _ => return,
+        };
+
+        let hash = if announced.caps2 {
+            caps2_hash(&info, &announced.algo)
+        } else {
+            caps_hash(&info, &announced.algo)
+        };
+        // Capabilities failing verification are not cached
+        if has_duplicates(&info) || hash.as_deref() != Some(&announced.value[..]) {
+            return;
+        }
+
+        info.node = None;
+        self.caps_store.insert(&node, info);
+        for jid in waiting {
+            self.entity_caps.insert(jid, node.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disco_info(xml: &str) -> DiscoInfo {
+        let elem: xml::Element = xml.parse().unwrap();
+        <DiscoInfo as Payload>::try_from(&elem).unwrap()
+    }
+
+    #[test]
+    fn caps_simple_example() {
+        // XEP-0115 §5.2
+        let info = disco_info(
+            "<query xmlns='http://jabber.org/protocol/disco#info'>\
+               <identity category='client' type='pc' name='Exodus 0.9.1'/>\
+               <feature var='http://jabber.org/protocol/caps'/>\
+               <feature var='http://jabber.org/protocol/disco#info'/>\
+               <feature var='http://jabber.org/protocol/disco#items'/>\
+               <feature var='http://jabber.org/protocol/muc'/>\
+             </query>",
+        );
+        assert_eq!(
+            caps_hash(&info, "sha-1").as_deref(),
+            Some("QgayPKawpkPSDYmwT/WM94uAlu0=")
+        );
+    }
+
+    #[test]
+    fn caps_complex_example() {
+        // XEP-0115 §5.3
+        let info = disco_info(
+            "<query xmlns='http://jabber.org/protocol/disco#info'>\
+               <identity xml:lang='en' category='client' name='Psi 0.11' type='pc'/>\
+               <identity xml:lang='el' category='client' name='Ψ 0.11' type='pc'/>\
+               <feature var='http://jabber.org/protocol/caps'/>\
+               <feature var='http://jabber.org/protocol/disco#info'/>\
+               <feature var='http://jabber.org/protocol/disco#items'/>\
+               <feature var='http://jabber.org/protocol/muc'/>\
+               <x xmlns='jabber:x:data' type='result'>\
+                 <field var='FORM_TYPE' type='hidden'>\
+                   <value>urn:xmpp:dataforms:softwareinfo</value>\
+                 </field>\
+                 <field var='ip_version'><value>ipv4</value><value>ipv6</value></field>\
+                 <field var='os'><value>Mac</value></field>\
+                 <field var='os_version'><value>10.5.1</value></field>\
+                 <field var='software'><value>Psi</value></field>\
+                 <field var='software_version'><value>0.11</value></field>\
+               </x>\
+             </query>",
+        );
+        assert_eq!(
+            caps_hash(&info, "sha-1").as_deref(),
+            Some("q07IKJEyjvHSyhy//CH0CxmKi8w=")
+        );
+    }
+
+    #[test]
+    fn caps2_simple_example() {
+        // XEP-0390, simple example
+        let info = disco_info(
+            "<query xmlns='http://jabber.org/protocol/disco#info'>\
+               <identity category='client' name='BombusMod' type='mobile'/>\
+               <feature var='http://jabber.org/protocol/si'/>\
+               <feature var='http://jabber.org/protocol/bytestreams'/>\
+               <feature var='http://jabber.org/protocol/chatstates'/>\
+               <feature var='http://jabber.org/protocol/disco#info'/>\
+               <feature var='http://jabber.org/protocol/disco#items'/>\
+               <feature var='urn:xmpp:ping'/>\
+               <feature var='jabber:iq:time'/>\
+               <feature var='jabber:iq:privacy'/>\
+               <feature var='jabber:iq:version'/>\
+               <feature var='http://jabber.org/protocol/rosterx'/>\
+               <feature var='urn:xmpp:time'/>\
+               <feature var='jabber:x:oob'/>\
+               <feature var='http://jabber.org/protocol/ibb'/>\
+               <feature var='http://jabber.org/protocol/si/profile/file-transfer'/>\
+               <feature var='urn:xmpp:receipts'/>\
+               <feature var='jabber:iq:roster'/>\
+               <feature var='jabber:iq:last'/>\
+             </query>",
+        );
+        assert_eq!(
+            caps2_hash(&info, "sha-256").as_deref(),
+            Some("kzBZbkqJ3ADrj7v08reD1qcWUwNGHaidNUgD7nHpiw8=")
+        );
+        assert_eq!(
+            caps2_hash(&info, "sha3-256").as_deref(),
+            Some("79mdYAfU9rEdTOcWDO7UEAt6E56SUzk/g6TnqUeuD9Q=")
+        );
+    }
+
+    #[test]
+    fn duplicate_form_types() {
+        // XEP-0115 §5.4, e.g. two forms with the same FORM_TYPE are invalid
+        let form = "<x xmlns='jabber:x:data' type='result'>\
+                      <field var='FORM_TYPE' type='hidden'>\
+                        <value>urn:xmpp:dataforms:softwareinfo</value>\
+                      </field>\
+                      <field var='software'><value>Psi</value></field>\
+                    </x>";
+        let query = |forms: &str| {
+            disco_info(&format!(
+                "<query xmlns='http://jabber.org/protocol/disco#info'>\
+                   <identity category='client' type='pc'/>\
+                   <feature var='http://jabber.org/protocol/caps'/>\
+                   {}\
+                 </query>",
+                forms
+            ))
+        };
+        assert!(!has_duplicates(&query(form)));
+        assert!(has_duplicates(&query(&form.repeat(2))));
+    }
+}
@@ -0,0 +1,140 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! Message Carbons (XEP-0280)
+
+use std::io;
+
+use crate::jid;
+use crate::ns;
+use crate::stanzas::{Forwarded, Iq, IqType, Message, Payload, Stanza};
+use crate::{iq_result, Event, PendingIqKind, XmppHandler, XmppStream};
+
+/// The `<private xmlns='urn:xmpp:carbons:2'/>` payload.
+///
+/// Keeps the server from sending carbon copies of an outgoing message to our
+/// other resources. See `mark_private()`, which also adds the `NoCopy` hint.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Private;
+
+impl Payload for Private {
+    const NAME: &'static str = "private";
+    const NS: &'static str = ns::CARBONS;
+
+    fn try_from(elem: &xml::Element) -> Result<Private, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a private element");
+        }
+        Ok(Private)
+    }
+
+    fn to_element(&self) -> xml::Element {
+        xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), vec![])
+    }
+}
+
+/// The `<no-copy xmlns='urn:xmpp:hints'/>` payload (XEP-0334)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NoCopy;
+
+impl Payload for NoCopy {
+    const NAME: &'static str = "no-copy";
+    const NS: &'static str = ns::HINTS;
+
+    fn try_from(elem: &xml::Element) -> Result<NoCopy, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a no-copy element");
+        }
+        Ok(NoCopy)
+    }
+
+    fn to_element(&self) -> xml::Element {
+        xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), vec![])
+    }
+}
+
+/// Excludes `message` from carbons, adding the `NoCopy` hint as XEP-0280 asks
+/// senders to
+pub fn mark_private(message: &mut Message) {
+    message.add_payload(Private);
+    message.add_payload(NoCopy);
+}
+
+impl XmppStream {
+    /// Asks the server to copy messages to and from our other resources to this one.
+    ///
+    /// Returns the id of the request, the outcome is reported by `Event::CarbonsResult`.
+    /// Copies are reported by `Event::CarbonReceived` and `Event::CarbonSent`.
+    pub fn enable_carbons(&mut self) -> io::Result<String> {
+        self.handler.send_carbons_toggle(true)
+    }
+
+    /// Stops copies of messages of our other resources.
+    ///
+    /// Returns the id of the request, the outcome is reported by `Event::CarbonsResult`.
+    pub fn disable_carbons(&mut self) -> io::Result<String> {
+        self.handler.send_carbons_toggle(false)
+    }
+
+    /// Whether carbons are enabled for this session
+    pub fn carbons_enabled(&self) -> bool {
+        self.handler.carbons
+    }
+}
+
+impl XmppHandler {
+    fn send_carbons_toggle(&mut self, enable: bool) -> io::Result<String> {
+        let name = if enable { "enable" } else { "disable" };
+        let mut iq = Iq::new(IqType::Set, String::new());
+        iq.as_element_mut().tag(xml::Element::new(
+            name.into(),
+            Some(ns::CARBONS.into()),
+            vec![],
+        ));
+        self.send_iq(iq, PendingIqKind::Carbons(enable))
+    }
+
+    pub(crate) fn handle_carbons_result(&mut self, enabled: bool, iq: Iq) {
+        let result = iq_result(&iq);
+        if result.is_ok() {
+            self.carbons = enabled;
+        }
+        let id = iq.id().unwrap().into();
+        self.events.push_back(Event::CarbonsResult {
+            id,
+            enabled,
+            result,
+        });
+    }
+
+    /// Unwraps carbon copies, returns all other messages
+    pub(crate) fn handle_carbon(&mut self, message: Message) -> Option<Message> {
+        let (sent, carbon) = match message.get_child("received", Some(ns::CARBONS)) {
+            Some(received) => (false, received),
+            None => match message.get_child("sent", Some(ns::CARBONS)) {
+                Some(sent) => (true, sent),
+                None => return Some(message),
+            },
+        };
+        // Anyone else could claim we sent or received arbitrary messages, such
+        // copies are dropped
+        let own_bare = self.jid.as_deref().map(jid::bare);
+        if message.from().is_none() || message.from() != own_bare {
+            return None;
+        }
+        let copy = carbon
+            .get_child(Forwarded::NAME, Some(Forwarded::NS))
+            .and_then(|forwarded| <Forwarded as Payload>::try_from(forwarded).ok())
+            .and_then(|forwarded| forwarded.into_message());
+        // Malformed copies are dropped
+        match copy {
+            Some(copy) if sent => self.events.push_back(Event::CarbonSent(copy)),
+            Some(copy) => self.events.push_back(Event::CarbonReceived(copy)),
+            None => (),
+        }
+        None
+    }
+}
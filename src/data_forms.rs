@@ -0,0 +1,466 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! Data Forms (XEP-0004)
+
+use std::fmt;
+
+use crate::ns;
+use crate::stanzas::Payload;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FormType {
+    /// A form to be filled out
+    Form,
+    /// A filled out form
+    Submit,
+    /// The form was not filled out
+    Cancel,
+    /// Data returned by a query
+    Result,
+}
+
+impl FormType {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            FormType::Form => "form",
+            FormType::Submit => "submit",
+            FormType::Cancel => "cancel",
+            FormType::Result => "result",
+        }
+    }
+
+    fn from_str(ty: &str) -> Option<FormType> {
+        match ty {
+            "form" => Some(FormType::Form),
+            "submit" => Some(FormType::Submit),
+            "cancel" => Some(FormType::Cancel),
+            "result" => Some(FormType::Result),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    Boolean,
+    /// Text for display only
+    Fixed,
+    /// Not shown to the user, but returned with the form
+    Hidden,
+    JidMulti,
+    JidSingle,
+    ListMulti,
+    ListSingle,
+    TextMulti,
+    /// Text that is obscured, e.g. a password
+    TextPrivate,
+    TextSingle,
+}
+
+impl FieldType {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            FieldType::Boolean => "boolean",
+            FieldType::Fixed => "fixed",
+            FieldType::Hidden => "hidden",
+            FieldType::JidMulti => "jid-multi",
+            FieldType::JidSingle => "jid-single",
+            FieldType::ListMulti => "list-multi",
+            FieldType::ListSingle => "list-single",
+            FieldType::TextMulti => "text-multi",
+            FieldType::TextPrivate => "text-private",
+            FieldType::TextSingle => "text-single",
+        }
+    }
+
+    fn from_str(ty: &str) -> Option<FieldType> {
+        match ty {
+            "boolean" => Some(FieldType::Boolean),
+            "fixed" => Some(FieldType::Fixed),
+            "hidden" => Some(FieldType::Hidden),
+            "jid-multi" => Some(FieldType::JidMulti),
+            "jid-single" => Some(FieldType::JidSingle),
+            "list-multi" => Some(FieldType::ListMulti),
+            "list-single" => Some(FieldType::ListSingle),
+            "text-multi" => Some(FieldType::TextMulti),
+            "text-private" => Some(FieldType::TextPrivate),
+            "text-single" => Some(FieldType::TextSingle),
+            _ => None,
+        }
+    }
+
+    /// Whether fields of this type may have more than one value
+    pub fn is_multi(&self) -> bool {
+        matches!(
+            *self,
+            FieldType::JidMulti | FieldType::ListMulti | FieldType::TextMulti
+        )
+    }
+}
+
+/// A choice of a `list-single` or `list-multi` field
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldOption {
+    pub label: Option<String>,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Field {
+    /// Identifies the field, only `fixed` fields may lack it
+    pub var: Option<String>,
+    /// `None` is treated as `FieldType::TextSingle`
+    pub ty: Option<FieldType>,
+    pub label: Option<String>,
+    pub desc: Option<String>,
+    pub required: bool,
+    pub values: Vec<String>,
+    pub options: Vec<FieldOption>,
+}
+
+impl Field {
+    pub fn new(var: &str, ty: FieldType) -> Field {
+        Field {
+            var: Some(var.into()),
+            ty: Some(ty),
+            ..Field::default()
+        }
+    }
+
+    /// A field with a single value, as used in submitted forms
+    pub fn with_value(var: &str, value: &str) -> Field {
+        Field {
+            var: Some(var.into()),
+            values: vec![value.into()],
+            ..Field::default()
+        }
+    }
+
+    /// The type, defaulting to `text-single`
+    pub fn field_type(&self) -> FieldType {
+        self.ty.unwrap_or(FieldType::TextSingle)
+    }
+
+    /// The first value, if any
+    pub fn value(&self) -> Option<&str> {
+        self.values.first().map(|x| &x[..])
+    }
+
+    /// The value of a `boolean` field, `None` if missing or invalid
+    pub fn bool_value(&self) -> Option<bool> {
+        match self.value() {
+            Some("1") | Some("true") => Some(true),
+            Some("0") | Some("false") => Some(false),
+            _ => None,
+        }
+    }
+
+    fn from_element(field: &xml::Element) -> Result<Field, &'static str> {
+        let ty = match field.get_attribute("type", None) {
+            Some(ty) => Some(FieldType::from_str(ty).ok_or("Invalid field type")?),
+            None => None,
+        };
+        let child_text = |name| {
+            field
+                .get_child(name, Some(ns::DATA_FORMS))
+                .map(|child| child.content_str())
+        };
+        let options = field
+            .get_children("option", Some(ns::DATA_FORMS))
+            .map(|option| {
+                let value = option
+                    .get_child("value", Some(ns::DATA_FORMS))
+                    .ok_or("Option without value")?;
+                Ok(FieldOption {
+                    label: option.get_attribute("label", None).map(|x| x.into()),
+                    value: value.content_str(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Field {
+            var: field.get_attribute("var", None).map(|x| x.into()),
+            ty,
+            label: field.get_attribute("label", None).map(|x| x.into()),
+            desc: child_text("desc"),
+            required: field.get_child("required", Some(ns::DATA_FORMS)).is_some(),
+            values: field
+                .get_children("value", Some(ns::DATA_FORMS))
+                .map(|value| value.content_str())
+                .collect(),
+            options,
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut attrs = vec![];
+        if let Some(ref var) = self.var {
+            attrs.push(("var".into(), None, var.clone()));
+        }
+        if let Some(ty) = self.ty {
+            attrs.push(("type".into(), None, ty.as_str().into()));
+        }
+        if let Some(ref label) = self.label {
+            attrs.push(("label".into(), None, label.clone()));
+        }
+        let mut field = xml::Element::new("field".into(), Some(ns::DATA_FORMS.into()), attrs);
+        if let Some(ref desc) = self.desc {
+            field
+                .tag(xml::Element::new(
+                    "desc".into(),
+                    Some(ns::DATA_FORMS.into()),
+                    vec![],
+                ))
+                .text(desc.clone());
+        }
+        if self.required {
+            field.tag(xml::Element::new(
+                "required".into(),
+                Some(ns::DATA_FORMS.into()),
+                vec![],
+            ));
+        }
+        for value in &self.values {
+            field
+                .tag(xml::Element::new(
+                    "value".into(),
+                    Some(ns::DATA_FORMS.into()),
+                    vec![],
+                ))
+                .text(value.clone());
+        }
+        for option in &self.options {
+            let attrs = option
+                .label
+                .clone()
+                .map(|label| ("label".into(), None, label));
+            field
+                .tag(xml::Element::new(
+                    "option".into(),
+                    Some(ns::DATA_FORMS.into()),
+                    attrs,
+                ))
+                .tag(xml::Element::new(
+                    "value".into(),
+                    Some(ns::DATA_FORMS.into()),
+                    vec![],
+                ))
+                .text(option.value.clone());
+        }
+        field
+    }
+}
+
+/// Why a form failed validation, naming the offending field
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FormError {
+    /// A required field has no value
+    MissingValue(String),
+    /// A single-valued field has several values
+    TooManyValues(String),
+    /// A value is not valid for the type of the field
+    InvalidValue(String),
+}
+
+impl fmt::Display for FormError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FormError::MissingValue(ref var) => write!(f, "Field '{}' is required", var),
+            FormError::TooManyValues(ref var) => {
+                write!(f, "Field '{}' only allows a single value", var)
+            }
+            FormError::InvalidValue(ref var) => write!(f, "Field '{}' has an invalid value", var),
+        }
+    }
+}
+
+/// The `<x xmlns='jabber:x:data'/>` payload
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DataForm {
+    pub ty: FormType,
+    pub title: Option<String>,
+    pub instructions: Vec<String>,
+    pub fields: Vec<Field>,
+    /// The columns of a multi-item `result`
+    pub reported: Vec<Field>,
+    /// The rows of a multi-item `result`
+    pub items: Vec<Vec<Field>>,
+}
+
+impl DataForm {
+    pub fn new(ty: FormType) -> DataForm {
+        DataForm {
+            ty,
+            title: None,
+            instructions: vec![],
+            fields: vec![],
+            reported: vec![],
+            items: vec![],
+        }
+    }
+
+    /// The value of the hidden `FORM_TYPE` field (XEP-0068)
+    pub fn form_type(&self) -> Option<&str> {
+        self.field("FORM_TYPE").and_then(|field| field.value())
+    }
+
+    pub fn field(&self, var: &str) -> Option<&Field> {
+        self.fields
+            .iter()
+            .find(|field| field.var.as_deref() == Some(var))
+    }
+
+    pub fn field_mut(&mut self, var: &str) -> Option<&mut Field> {
+        self.fields
+            .iter_mut()
+            .find(|field| field.var.as_deref() == Some(var))
+    }
+
+    /// Replaces the values of the field `var`, returns `false` if there is no such field
+    pub fn set_values(&mut self, var: &str, values: Vec<String>) -> bool {
+        match self.field_mut(var) {
+            Some(field) => {
+                field.values = values;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Checks that required fields have values, and values fit their field types
+    pub fn validate(&self) -> Result<(), FormError> {
+        for field in &self.fields {
+            let var = match field.var {
+                Some(ref var) => var,
+                None => continue,
+            };
+            let ty = field.field_type();
+            if field.required && field.values.iter().all(|value| value.is_empty()) {
+                return Err(FormError::MissingValue(var.clone()));
+            }
+            if !ty.is_multi() && field.values.len() > 1 {
+                return Err(FormError::TooManyValues(var.clone()));
+            }
+            let valid = match ty {
+                FieldType::Boolean => field.values.is_empty() || field.bool_value().is_some(),
+                FieldType::ListSingle | FieldType::ListMulti if !field.options.is_empty() => field
+                    .values
+                    .iter()
+                    .all(|value| field.options.iter().any(|option| option.value == *value)),
+                _ => true,
+            };
+            if !valid {
+                return Err(FormError::InvalidValue(var.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// The filled out form to send back, only containing the values
+    pub fn to_submit(&self) -> DataForm {
+        let mut submit = DataForm::new(FormType::Submit);
+        submit.fields = self
+            .fields
+            .iter()
+            .filter(|field| field.var.is_some() && field.field_type() != FieldType::Fixed)
+            .map(|field| Field {
+                var: field.var.clone(),
+                values: field.values.clone(),
+                ..Field::default()
+            })
+            .collect();
+        submit
+    }
+}
+
+impl Payload for DataForm {
+    const NAME: &'static str = "x";
+    const NS: &'static str = ns::DATA_FORMS;
+
+    fn try_from(elem: &xml::Element) -> Result<DataForm, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a data form");
+        }
+        let ty = elem
+            .get_attribute("type", None)
+            .ok_or("Form without type")?;
+        let fields = |parent: &xml::Element| {
+            parent
+                .get_children("field", Some(Self::NS))
+                .map(Field::from_element)
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let reported = match elem.get_child("reported", Some(Self::NS)) {
+            Some(reported) => fields(reported)?,
+            None => vec![],
+        };
+        let items = elem
+            .get_children("item", Some(Self::NS))
+            .map(fields)
+            .collect::<Result<_, _>>()?;
+        Ok(DataForm {
+            ty: FormType::from_str(ty).ok_or("Invalid form type")?,
+            title: elem
+                .get_child("title", Some(Self::NS))
+                .map(|title| title.content_str()),
+            instructions: elem
+                .get_children("instructions", Some(Self::NS))
+                .map(|instructions| instructions.content_str())
+                .collect(),
+            fields: fields(elem)?,
+            reported,
+            items,
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut x = xml::Element::new(
+            Self::NAME.into(),
+            Some(Self::NS.into()),
+            vec![("type".into(), None, self.ty.as_str().into())],
+        );
+        if let Some(ref title) = self.title {
+            x.tag(xml::Element::new(
+                "title".into(),
+                Some(Self::NS.into()),
+                vec![],
+            ))
+            .text(title.clone());
+        }
+        for instructions in &self.instructions {
+            x.tag(xml::Element::new(
+                "instructions".into(),
+                Some(Self::NS.into()),
+                vec![],
+            ))
+            .text(instructions.clone());
+        }
+        for field in &self.fields {
+            x.tag(field.to_element());
+        }
+        if !self.reported.is_empty() {
+            let reported = x.tag(xml::Element::new(
+                "reported".into(),
+                Some(Self::NS.into()),
+                vec![],
+            ));
+            for field in &self.reported {
+                reported.tag(field.to_element());
+            }
+        }
+        for item in &self.items {
+            let elem = x.tag(xml::Element::new(
+                "item".into(),
+                Some(Self::NS.into()),
+                vec![],
+            ));
+            for field in item {
+                elem.tag(field.to_element());
+            }
+        }
+        x
+    }
+}
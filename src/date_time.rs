@@ -0,0 +1,181 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! XMPP Date and Time Profiles (XEP-0082)
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECS_PER_DAY: i64 = 86400;
+
+/// Parses a `DateTime`, e.g. `1969-07-21T02:56:15.123Z` or `1969-07-20T21:56:15-05:00`
+pub fn parse(date_time: &str) -> Option<SystemTime> {
+    if !date_time.is_ascii() {
+        return None;
+    }
+    let (date, time) = date_time.split_once('T')?;
+    let mut date = date.splitn(3, '-');
+    let year = number(date.next()?, 4)?;
+    let month = number(date.next()?, 2)?;
+    let day = number(date.next()?, 2)?;
+
+    let (time, offset) = match time.strip_suffix('Z') {
+        Some(time) => (time, 0),
+        None => {
+            let (time, tzd) = time.split_at(time.len().checked_sub(6)?);
+            let sign = match tzd.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let (hours, minutes) = tzd[1..].split_once(':')?;
+            (
+                time,
+                sign * (number(hours, 2)? * 3600 + number(minutes, 2)? * 60),
+            )
+        }
+    };
+    let (time, fraction) = match time.split_once('.') {
+        Some((time, fraction)) => (time, Some(fraction)),
+        None => (time, None),
+    };
+    let mut time = time.splitn(3, ':');
+    let hour = number(time.next()?, 2)?;
+    let minute = number(time.next()?, 2)?;
+    let second = number(time.next()?, 2)?;
+    let nanos = match fraction {
+        Some("") => return None,
+        Some(fraction) => {
+            // Digits beyond nanoseconds are dropped
+            let digits = &fraction[..fraction.len().min(9)];
+            let value = number(digits, digits.len())?;
+            value as u32 * 10u32.pow(9 - digits.len() as u32)
+        }
+        None => 0,
+    };
+
+    let days = days_from_civil(year, month, day);
+    // Rejects e.g. February 30th, which would silently roll over
+    if civil_from_days(days) != (year, month, day) || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let secs = days * SECS_PER_DAY + hour * 3600 + minute * 60 + second - offset;
+    let since_epoch = Duration::from_secs(secs.unsigned_abs());
+    let time = if secs >= 0 {
+        UNIX_EPOCH + since_epoch
+    } else {
+        UNIX_EPOCH - since_epoch
+    };
+    Some(time + Duration::from_nanos(nanos.into()))
+}
+
+/// Formats `time` as `DateTime` in UTC, with milliseconds if there are any
+pub fn format(time: SystemTime) -> String {
+    let (secs, nanos) = match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => (since.as_secs() as i64, since.subsec_nanos()),
+        Err(err) => {
+            let before = err.duration();
+            let secs = -(before.as_secs() as i64);
+            match before.subsec_nanos() {
+                0 => (secs, 0),
+                nanos => (secs - 1, 1_000_000_000 - nanos),
+            }
+        }
+    };
+    let (year, month, day) = civil_from_days(secs.div_euclid(SECS_PER_DAY));
+    let secs = secs.rem_euclid(SECS_PER_DAY);
+    let mut formatted = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    );
+    if nanos >= 1_000_000 {
+        formatted.push_str(&format!(".{:03}", nanos / 1_000_000));
+    }
+    formatted.push('Z');
+    formatted
+}
+
+/// Parses a number of exactly `digits` decimal digits
+fn number(s: &str, digits: usize) -> Option<i64> {
+    if s.len() != digits || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// The days since 1970-01-01 of a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The inverse of `days_from_civil()`
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_examples() {
+        // XEP-0082 §3.2, the same instant in UTC and with an offset
+        let utc = parse("1969-07-21T02:56:15Z").unwrap();
+        let offset = parse("1969-07-20T21:56:15-05:00").unwrap();
+        assert_eq!(utc, UNIX_EPOCH - Duration::from_secs(14159025));
+        assert_eq!(utc, offset);
+        assert_eq!(parse("1970-01-01T01:00:00+01:00"), Some(UNIX_EPOCH));
+    }
+
+    #[test]
+    fn parse_fractional_seconds() {
+        let time = parse("2002-09-10T23:41:07.123Z").unwrap();
+        assert_eq!(time, UNIX_EPOCH + Duration::from_millis(1031701267123));
+        let before_epoch = parse("1969-12-31T23:59:59.5Z").unwrap();
+        assert_eq!(before_epoch, UNIX_EPOCH - Duration::from_millis(500));
+        assert_eq!(parse("1970-01-01T00:00:00.Z"), None);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(parse("2000-02-30T00:00:00Z"), None);
+        assert_eq!(parse("1970-01-01T24:00:00Z"), None);
+        assert_eq!(parse("19700101T00:00:00Z"), None);
+        assert!(parse("2000-02-29T00:00:00Z").is_some());
+    }
+
+    #[test]
+    fn parse_missing_tzd() {
+        assert_eq!(parse("1969-07-21T02:56:15"), None);
+        assert_eq!(parse("1969-07-21T02:56:15.123"), None);
+    }
+
+    #[test]
+    fn format_round_trip() {
+        for stamp in ["1969-07-21T02:56:15Z", "2002-09-10T23:41:07.123Z"] {
+            assert_eq!(format(parse(stamp).unwrap()), stamp);
+        }
+    }
+}
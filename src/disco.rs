@@ -0,0 +1,411 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! Service Discovery (XEP-0030)
+
+use std::io;
+
+use crate::data_forms::DataForm;
+use crate::ns;
+use crate::stanzas::{DefinedCondition, ErrorType, Iq, IqType, Pager, Payload, ResultSet, Stanza};
+use crate::{iq_error, malformed, Event, PendingIqKind, XmppHandler, XmppStream};
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Identity {
+    pub category: String,
+    pub ty: String,
+    pub lang: Option<String>,
+    pub name: Option<String>,
+}
+
+impl Identity {
+    pub fn new(category: &str, ty: &str, name: Option<&str>) -> Identity {
+        Identity {
+            category: category.into(),
+            ty: ty.into(),
+            lang: None,
+            name: name.map(|x| x.into()),
+        }
+    }
+
+    fn from_element(identity: &xml::Element) -> Result<Identity, &'static str> {
+        let attr = |name| identity.get_attribute(name, None).map(|x| x.to_string());
+        Ok(Identity {
+            category: attr("category").ok_or("Identity without category")?,
+            ty: attr("type").ok_or("Identity without type")?,
+            lang: identity
+                .get_attribute("lang", Some(ns::XML))
+                .map(|x| x.into()),
+            name: attr("name"),
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut attrs = vec![
+            ("category".into(), None, self.category.clone()),
+            ("type".into(), None, self.ty.clone()),
+        ];
+        if let Some(ref lang) = self.lang {
+            attrs.push(("lang".into(), Some(ns::XML.into()), lang.clone()));
+        }
+        if let Some(ref name) = self.name {
+            attrs.push(("name".into(), None, name.clone()));
+        }
+        xml::Element::new("identity".into(), Some(ns::DISCO_INFO.into()), attrs)
+    }
+}
+
+/// The `<query xmlns='http://jabber.org/protocol/disco#info'/>` payload
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiscoInfo {
+    pub node: Option<String>,
+    pub identities: Vec<Identity>,
+    pub features: Vec<String>,
+    /// Extended information (XEP-0128)
+    pub extensions: Vec<DataForm>,
+}
+
+impl DiscoInfo {
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
+
+impl Payload for DiscoInfo {
+    const NAME: &'static str = "query";
+    const NS: &'static str = ns::DISCO_INFO;
+
+    fn try_from(elem: &xml::Element) -> Result<DiscoInfo, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a disco#info query");
+        }
+        let identities = elem
+            .get_children("identity", Some(Self::NS))
+            .map(Identity::from_element)
+            .collect::<Result<_, _>>()?;
+        let features = elem
+            .get_children("feature", Some(Self::NS))
+            .filter_map(|feature| feature.get_attribute("var", None))
+            .map(|var| var.into())
+            .collect();
+        let extensions = elem
+            .get_children("x", Some(ns::DATA_FORMS))
+            .map(<DataForm as Payload>::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(DiscoInfo {
+            node: elem.get_attribute("node", None).map(|x| x.into()),
+            identities,
+            features,
+            extensions,
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let attrs = self.node.clone().map(|node| ("node".into(), None, node));
+        let mut query = xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), attrs);
+        for identity in &self.identities {
+            query.tag(identity.to_element());
+        }
+        for feature in &self.features {
+            query.tag(xml::Element::new(
+                "feature".into(),
+                Some(Self::NS.into()),
+                vec![("var".into(), None, feature.clone())],
+            ));
+        }
+        for extension in &self.extensions {
+            query.tag(extension.to_element());
+        }
+        query
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiscoItem {
+    pub jid: String,
+    pub node: Option<String>,
+    pub name: Option<String>,
+}
+
+impl DiscoItem {
+    fn from_element(item: &xml::Element) -> Result<DiscoItem, &'static str> {
+        let attr = |name| item.get_attribute(name, None).map(|x| x.to_string());
+        Ok(DiscoItem {
+            jid: attr("jid").ok_or("Disco item without JID")?,
+            node: attr("node"),
+            name: attr("name"),
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut attrs = vec![("jid".into(), None, self.jid.clone())];
+        if let Some(ref node) = self.node {
+            attrs.push(("node".into(), None, node.clone()));
+        }
+        if let Some(ref name) = self.name {
+            attrs.push(("name".into(), None, name.clone()));
+        }
+        xml::Element::new("item".into(), Some(ns::DISCO_ITEMS.into()), attrs)
+    }
+}
+
+/// The `<query xmlns='http://jabber.org/protocol/disco#items'/>` payload
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiscoItems {
+    pub node: Option<String>,
+    pub items: Vec<DiscoItem>,
+    /// Selects or describes a page of the items
+    pub set: Option<ResultSet>,
+}
+
+impl Payload for DiscoItems {
+    const NAME: &'static str = "query";
+    const NS: &'static str = ns::DISCO_ITEMS;
+
+    fn try_from(elem: &xml::Element) -> Result<DiscoItems, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a disco#items query");
+        }
+        let items = elem
+            .get_children("item", Some(Self::NS))
+            .map(DiscoItem::from_element)
+            .collect::<Result<_, _>>()?;
+        let set = match elem.get_child(ResultSet::NAME, Some(ResultSet::NS)) {
+            Some(set) => Some(<ResultSet as Payload>::try_from(set)?),
+            None => None,
+        };
+        Ok(DiscoItems {
+            node: elem.get_attribute("node", None).map(|x| x.into()),
+            items,
+            set,
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let attrs = self.node.clone().map(|node| ("node".into(), None, node));
+        let mut query = xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), attrs);
+        for item in &self.items {
+            query.tag(item.to_element());
+        }
+        if let Some(ref set) = self.set {
+            query.tag(set.to_element());
+        }
+        query
+    }
+}
+
+/// A multi-page disco#items query in progress
+pub(crate) struct DiscoPaging {
+    /// The id reported for the whole query
+    id: String,
+    pager: Pager,
+    /// The items of the pages so far
+    items: Vec<DiscoItem>,
+}
+
+/// The disco#info we advertise for our own JID
+pub(crate) fn default_info() -> DiscoInfo {
+    DiscoInfo {
+        node: None,
+        identities: vec![Identity::new("client", "pc", Some("rust-xmpp"))],
+        features: vec![
+            ns::CAPS.into(),
+            ns::CAPS2.into(),
+            ns::DISCO_INFO.into(),
+            ns::DISCO_ITEMS.into(),
+            ns::MUC.into(),
+            ns::PING.into(),
+            ns::RECEIPTS.into(),
+        ],
+        extensions: vec![],
+    }
+}
+
+impl XmppStream {
+    /// Queries the identities and features of `jid`, or one of its nodes.
+    ///
+    /// Returns the id of the request, the response is reported by `Event::DiscoInfo`.
+    pub fn query_disco_info(&mut self, jid: &str, node: Option<&str>) -> io::Result<String> {
+        let mut iq = Iq::new(IqType::Get, String::new());
+        iq.set_to(Some(jid.into()));
+        iq.add_payload(DiscoInfo {
+            node: node.map(|x| x.into()),
+            ..DiscoInfo::default()
+        });
+        self.handler.send_iq(iq, PendingIqKind::DiscoInfo)
+    }
+
+    /// Queries the items associated with `jid`, or one of its nodes.
+    ///
+    /// Returns the id of the request, the response is reported by `Event::DiscoItems`.
+    pub fn query_disco_items(&mut self, jid: &str, node: Option<&str>) -> io::Result<String> {
+        let mut iq = Iq::new(IqType::Get, String::new());
+        iq.set_to(Some(jid.into()));
+        iq.add_payload(DiscoItems {
+            node: node.map(|x| x.into()),
+            items: vec![],
+            set: None,
+        });
+        self.handler.send_iq(iq, PendingIqKind::DiscoItems(None))
+    }
+
+    /// Queries the items associated with `jid` page by page, as selected by `pager`.
+    ///
+    /// Returns the id of the request. Once all pages were fetched, the items
+    /// are reported together by `Event::DiscoItems`, with the result set of
+    /// the last page.
+    pub fn fetch_disco_items(
+        &mut self,
+        jid: &str,
+        node: Option<&str>,
+        pager: Pager,
+    ) -> io::Result<String> {
+        let query = DiscoItems {
+            node: node.map(|x| x.into()),
+            items: vec![],
+            set: Some(pager.first()),
+        };
+        let id = self.handler.next_id();
+        let paging = DiscoPaging {
+            id: id.clone(),
+            pager,
+            items: vec![],
+        };
+        self.handler.send_disco_page(jid.into(), query, paging)?;
+        Ok(id)
+    }
+
+    /// The disco#info we answer queries for our own JID with
+    pub fn disco_info(&self) -> &DiscoInfo {
+        &self.handler.disco_info
+    }
+
+    /// Replaces the identities we advertise, by default `client/pc`
+    pub fn set_disco_identities(&mut self, identities: Vec<Identity>) {
+        self.handler.disco_info.identities = identities;
+    }
+
+    /// Advertises support for `feature` in our disco#info
+    pub fn add_disco_feature(&mut self, feature: &str) {
+        let features = &mut self.handler.disco_info.features;
+        if !features.iter().any(|f| f == feature) {
+            features.push(feature.into());
+        }
+    }
+
+    pub fn remove_disco_feature(&mut self, feature: &str) {
+        self.handler.disco_info.features.retain(|f| f != feature);
+    }
+
+    /// Adds an extended information form (XEP-0128) to our disco#info
+    pub fn add_disco_extension(&mut self, form: DataForm) {
+        self.handler.disco_info.extensions.push(form);
+    }
+}
+
+impl XmppHandler {
+    pub(crate) fn handle_disco_info_result(&mut self, jid: String, iq: Iq) {
+        let result = match iq.stanza_type() {
+            Some(IqType::Error) => Err(iq_error(&iq)),
+            _ => iq.get_payload::<DiscoInfo>().ok_or_else(malformed),
+        };
+        let id = iq.id().unwrap().into();
+        self.events.push_back(Event::DiscoInfo { id, jid, result });
+    }
+
+    pub(crate) fn handle_disco_items_result(
+        &mut self,
+        jid: String,
+        paging: Option<DiscoPaging>,
+        iq: Iq,
+    ) {
+        let mut result = match iq.stanza_type() {
+            Some(IqType::Error) => Err(iq_error(&iq)),
+            _ => iq.get_payload::<DiscoItems>().ok_or_else(malformed),
+        };
+        let id = match paging {
+            Some(mut paging) => {
+                if let Ok(ref mut query) = result {
+                    let received = query.items.len();
+                    paging.items.append(&mut query.items);
+                    if let Some(set) = paging.pager.next(query.set.as_ref(), received) {
+                        let next = DiscoItems {
+                            node: query.node.clone(),
+                            items: vec![],
+                            set: Some(set),
+                        };
+                        let _ = self.send_disco_page(jid, next, paging);
+                        return;
+                    }
+                    query.items = paging.items;
+                }
+                paging.id
+            }
+            None => iq.id().unwrap().into(),
+        };
+        self.events.push_back(Event::DiscoItems { id, jid, result });
+    }
+
+    fn send_disco_page(
+        &mut self,
+        jid: String,
+        query: DiscoItems,
+        paging: DiscoPaging,
+    ) -> io::Result<String> {
+        let mut iq = Iq::new(IqType::Get, String::new());
+        iq.set_to(Some(jid));
+        iq.add_payload(query);
+        self.send_iq(iq, PendingIqKind::DiscoItems(Some(paging)))
+    }
+
+    pub(crate) fn handle_disco_info_request(&mut self, iq: Iq) {
+        let node = iq.get_payload::<DiscoInfo>().and_then(|query| query.node);
+        // Entities verifying our capabilities query the node they were announced with
+        let (caps_node, caps2_node) = self.own_caps_nodes();
+        let reply = match node {
+            None => {
+                let mut reply = iq.result_reply();
+                reply.add_payload(self.disco_info.clone());
+                reply
+            }
+            Some(node) if node == caps_node || node == caps2_node => {
+                let mut reply = iq.result_reply();
+                reply.add_payload(DiscoInfo {
+                    node: Some(node),
+                    ..self.disco_info.clone()
+                });
+                reply
+            }
+            Some(node) => match self.command_info(&node, &self.command_requester(&iq)) {
+                Some(info) => {
+                    let mut reply = iq.result_reply();
+                    reply.add_payload(info);
+                    reply
+                }
+                None => iq.error_reply(ErrorType::Cancel, DefinedCondition::ItemNotFound, None),
+            },
+        };
+        let _ = self.send(reply);
+    }
+
+    pub(crate) fn handle_disco_items_request(&mut self, iq: Iq) {
+        let node = iq.get_payload::<DiscoItems>().and_then(|query| query.node);
+        let reply = match node {
+            None => {
+                let mut reply = iq.result_reply();
+                reply.add_payload(DiscoItems::default());
+                reply
+            }
+            Some(ref node) if node == ns::COMMANDS => {
+                let mut reply = iq.result_reply();
+                reply.add_payload(self.command_items(&self.command_requester(&iq)));
+                reply
+            }
+            Some(_) => iq.error_reply(ErrorType::Cancel, DefinedCondition::ItemNotFound, None),
+        };
+        let _ = self.send(reply);
+    }
+}
@@ -0,0 +1,82 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+use crate::ns;
+
+/// STARTTLS as offered in `<starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StartTlsFeature {
+    pub required: bool,
+}
+
+/// Legacy session establishment (RFC 3921)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionFeature {
+    /// The server marked session establishment as `<optional/>`
+    pub optional: bool,
+}
+
+/// The features advertised by the server in `<stream:features/>`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StreamFeatures {
+    pub starttls: Option<StartTlsFeature>,
+    /// SASL mechanisms in the order the server listed them
+    pub mechanisms: Vec<String>,
+    pub bind: bool,
+    pub session: Option<SessionFeature>,
+    /// Stream Management (XEP-0198)
+    pub sm: bool,
+    /// Client State Indication (XEP-0352)
+    pub csi: bool,
+    /// Roster versioning (RFC 6121)
+    pub roster_ver: bool,
+    /// Subscription pre-approval (RFC 6121)
+    pub pre_approval: bool,
+    /// In-Band Registration (XEP-0077)
+    pub register: bool,
+}
+
+impl StreamFeatures {
+    pub fn from_element(features: &xml::Element) -> StreamFeatures {
+        let starttls = features
+            .get_child("starttls", Some(ns::FEATURE_TLS))
+            .map(|tls| StartTlsFeature {
+                required: tls.get_child("required", Some(ns::FEATURE_TLS)).is_some(),
+            });
+
+        let mechanisms = features
+            .get_child("mechanisms", Some(ns::FEATURE_SASL))
+            .map(|mechs| {
+                mechs
+                    .get_children("mechanism", Some(ns::FEATURE_SASL))
+                    .map(|mech| mech.content_str())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let session = features
+            .get_child("session", Some(ns::FEATURE_SESSION))
+            .map(|session| SessionFeature {
+                optional: session
+                    .get_child("optional", Some(ns::FEATURE_SESSION))
+                    .is_some(),
+            });
+
+        let has = |name, ns| features.get_child(name, Some(ns)).is_some();
+
+        StreamFeatures {
+            starttls,
+            mechanisms,
+            bind: has("bind", ns::FEATURE_BIND),
+            session,
+            sm: has("sm", ns::FEATURE_SM),
+            csi: has("csi", ns::FEATURE_CSI),
+            roster_ver: has("ver", ns::FEATURE_ROSTER_VER),
+            pre_approval: has("sub", ns::FEATURE_PRE_APPROVAL),
+            register: has("register", ns::FEATURE_REGISTER),
+        }
+    }
+}
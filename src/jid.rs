@@ -0,0 +1,29 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! Helpers for JIDs in their string representation
+
+/// The bare JID (`localpart@domainpart`) of `jid`
+pub fn bare(jid: &str) -> &str {
+    match jid.find('/') {
+        Some(idx) => &jid[..idx],
+        None => jid,
+    }
+}
+
+/// The domainpart of `jid`
+pub fn domain(jid: &str) -> &str {
+    let bare = bare(jid);
+    match bare.find('@') {
+        Some(idx) => &bare[idx + 1..],
+        None => bare,
+    }
+}
+
+/// The resourcepart of `jid`, if any
+pub fn resource(jid: &str) -> Option<&str> {
+    jid.find('/').map(|idx| &jid[idx + 1..])
+}
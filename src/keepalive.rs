@@ -0,0 +1,90 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! Keepalives and timeouts for idle or dead connections
+
+use std::time::{Duration, Instant};
+
+use crate::non_stanzas::Whitespace;
+use crate::{XmppHandler, XmppStream};
+
+/// How long to wait for the server to close its stream by default
+pub const DEFAULT_CLOSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl XmppStream {
+    /// Considers the connection dead after `timeout` without incoming data.
+    ///
+    /// `None`, the default, waits indefinitely. Note that the server may not
+    /// send anything while we are idle, so this is best combined with a ping
+    /// keepalive using a shorter interval.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.handler.read_timeout = timeout;
+    }
+
+    /// Sends a single space after `interval` without outgoing data, `None` disables this.
+    ///
+    /// This keeps NATs and firewalls from dropping idle connections, and makes
+    /// the OS notice a broken connection when the write fails.
+    pub fn set_whitespace_keepalive(&mut self, interval: Option<Duration>) {
+        self.handler.whitespace_interval = interval;
+    }
+
+    /// Sets how long to wait for the server's `</stream:stream>` after closing
+    /// our stream, by default `DEFAULT_CLOSE_TIMEOUT`
+    pub fn set_close_timeout(&mut self, timeout: Duration) {
+        self.handler.close_timeout = timeout;
+    }
+}
+
+impl XmppHandler {
+    /// The time until the next timer expires, if any is running
+    pub(crate) fn next_timeout(&self) -> Option<Duration> {
+        let deadline = [
+            self.closing_since.map(|since| since + self.close_timeout),
+            self.read_timeout
+                .map(|timeout| self.last_received + timeout),
+            self.whitespace_deadline(),
+            self.ping_deadline(),
+            self.muc_ping_deadline(),
+        ]
+        .iter()
+        .flatten()
+        .min()
+        .cloned()?;
+        // A zero timeout is rejected by the socket
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        Some(timeout.max(Duration::from_millis(1)))
+    }
+
+    /// Runs expired timers, returns `false` if the connection is considered dead
+    pub(crate) fn handle_timers(&mut self) -> bool {
+        let now = Instant::now();
+        if let Some(since) = self.closing_since {
+            if since + self.close_timeout <= now {
+                return false;
+            }
+        }
+        if let Some(timeout) = self.read_timeout {
+            if self.last_received + timeout <= now {
+                return false;
+            }
+        }
+        let whitespace_due = self.whitespace_deadline().is_some_and(|d| d <= now);
+        if whitespace_due && self.send(Whitespace).is_err() {
+            return false;
+        }
+        self.check_muc_self_pings();
+        self.check_ping()
+    }
+
+    fn whitespace_deadline(&self) -> Option<Instant> {
+        if self.closed {
+            return None;
+        }
+        self.whitespace_interval
+            .map(|interval| self.last_sent + interval)
+    }
+}
@@ -10,25 +10,52 @@
 extern crate openssl;
 extern crate xml;
 
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use std::io;
-use std::io::{BufReader, Write};
+use std::io::Write;
 use std::net::TcpStream;
 use std::ops::Deref;
+use std::time::{Duration, Instant, SystemTime};
 
+use crate::adhoc::{Command, CommandSession, RegisteredCommand};
 use crate::auth::Authenticator;
 use crate::auth::{AnonAuth, PlainAuth, ScramAuth};
+use crate::caps::{Announced, Caps, CapsStore, MemoryCapsStore};
+use crate::disco::{DiscoInfo, DiscoItems, DiscoPaging};
+use crate::features::StreamFeatures;
+use crate::mam::{Fin, MamState};
+use crate::muc::{MucEvent, MucRequest, Room};
 use crate::non_stanzas::{AuthResponse, AuthStart, DefinedCondition, StreamEnd, StreamStart};
 use crate::non_stanzas::{StartTls, StreamError};
-use crate::read_str::ReadString;
-use crate::stanzas::{AStanza, IqType, Stanza};
+use crate::presence_tracker::PresenceTracker;
+use crate::read_str::{ReadString, StrReader};
+use crate::receipts::{PendingReceipt, ReceiptPolicy};
+use crate::roster::{Roster, RosterItem, RosterStore};
+use crate::stanzas::{AStanza, IqType, PresenceType, Stanza, StanzaError};
 use crate::xmpp_send::XmppSend;
 use crate::xmpp_socket::XmppSocket;
 
+pub mod adhoc;
 mod auth;
+pub mod caps;
+pub mod carbons;
+pub mod data_forms;
+pub mod date_time;
+pub mod disco;
+pub mod features;
+pub mod jid;
+pub mod keepalive;
+pub mod mam;
+pub mod muc;
 mod non_stanzas;
 pub mod ns;
+pub mod ping;
+pub mod presence_tracker;
 mod read_str;
+pub mod receipts;
+pub mod roster;
 pub mod stanzas;
+mod subscription;
 mod xmpp_send;
 mod xmpp_socket;
 
@@ -81,18 +108,169 @@ pub enum Event<'a> {
     /// field 1: client JID
     Bound(Option<String>),
     BindError(stanzas::Iq),
+    SessionError(stanzas::Iq),
+    /// The roster was fetched, see `XmppStream::roster()`
+    RosterReceived,
+    /// A roster item was added or updated by the server
+    RosterItemChanged(RosterItem),
+    /// A roster item was removed by the server, field 1: the item's JID
+    RosterItemRemoved(String),
+    /// The outcome of a roster request, field 1: the request's id.
+    /// Also reports a failed or malformed roster fetch.
+    RosterResult(String, Result<(), StanzaError>),
+    /// The roster could not be saved to the `RosterStore`
+    RosterStoreError(io::Error),
+    /// A contact asks to see our presence
+    SubscriptionRequest(stanzas::Presence),
+    /// A contact approved our subscription request, field 1: its bare JID
+    SubscriptionApproved(String),
+    /// A contact denied or cancelled our subscription, field 1: its bare JID
+    SubscriptionDenied(String),
+    /// A contact stopped its subscription to our presence, field 1: its bare JID
+    ContactUnsubscribed(String),
+    /// The response to a disco#info query
+    DiscoInfo {
+        id: String,
+        jid: String,
+        result: Result<DiscoInfo, StanzaError>,
+    },
+    /// The response to a disco#items query
+    DiscoItems {
+        id: String,
+        jid: String,
+        result: Result<DiscoItems, StanzaError>,
+    },
+    /// The response to an ad-hoc command request
+    Command {
+        id: String,
+        jid: String,
+        result: Result<Command, StanzaError>,
+    },
+    /// A message from the results of an archive query
+    ArchivedMessage {
+        query_id: String,
+        /// The id of the message in the archive
+        id: String,
+        /// When the message was archived
+        stamp: Option<SystemTime>,
+        message: stanzas::Message,
+    },
+    /// All results of an archive query were received
+    ArchiveQueryFinished {
+        query_id: String,
+        result: Result<Fin, StanzaError>,
+    },
+    /// The outcome of enabling or disabling carbons
+    CarbonsResult {
+        id: String,
+        enabled: bool,
+        result: Result<(), StanzaError>,
+    },
+    /// A copy of a message received by another of our resources
+    CarbonReceived(stanzas::Message),
+    /// A copy of a message sent by another of our resources
+    CarbonSent(stanzas::Message),
+    /// The recipient confirmed receiving the message with the given id
+    MessageDelivered(String),
+    Muc(MucEvent),
     StreamError(xml::Element),
     StreamClosed,
 }
 
+/// Progress of the stream negotiation
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NegotiationState {
+    /// Not connected, either not yet or no longer
+    Disconnected,
+    /// Waiting for the server's stream features
+    Connecting,
+    /// STARTTLS was requested, waiting for the server to proceed
+    TlsNegotiating,
+    /// SASL authentication is in progress
+    Authenticating,
+    /// Resource binding and, if required, session establishment is in progress
+    Binding,
+    /// The resource is bound, stanzas can be exchanged
+    Online,
+}
+
 struct XmppHandler {
     username: String,
     password: String,
     domain: String,
     closed: bool,
     socket: XmppSocket,
+    state: NegotiationState,
+    features: Option<StreamFeatures>,
+    authenticated: bool,
     authenticator: Option<Box<dyn Authenticator + 'static>>,
-    pending_bind_id: Option<String>,
+    jid: Option<String>,
+    default_lang: Option<String>,
+    next_id: u64,
+    pending_iqs: HashMap<String, PendingIq>,
+    events: VecDeque<Event<'static>>,
+    roster: Roster,
+    roster_store: Option<Box<dyn RosterStore>>,
+    subscription_requests: BTreeSet<String>,
+    presences: PresenceTracker,
+    disco_info: DiscoInfo,
+    caps_node: String,
+    caps_store: Box<dyn CapsStore>,
+    /// The verified caps node of each full JID
+    entity_caps: HashMap<String, String>,
+    /// Caps nodes being verified, with the JIDs that announced them
+    pending_caps: HashMap<String, (Announced, Vec<String>)>,
+    ping_interval: Option<Duration>,
+    ping_timeout: Duration,
+    /// When the last data was read from the socket
+    last_received: Instant,
+    /// When the unanswered keepalive ping was sent
+    pending_ping: Option<Instant>,
+    read_timeout: Option<Duration>,
+    whitespace_interval: Option<Duration>,
+    close_timeout: Duration,
+    /// When we sent our `</stream:stream>`
+    closing_since: Option<Instant>,
+    /// When the last data was written to the socket
+    last_sent: Instant,
+    rooms: BTreeMap<String, Room>,
+    muc_ping_interval: Option<Duration>,
+    commands: BTreeMap<String, RegisteredCommand>,
+    /// Multi-stage commands in progress, by session id
+    command_sessions: HashMap<String, CommandSession>,
+    /// Archive queries awaiting their results, by query id
+    mam_queries: HashMap<String, MamState>,
+    /// Whether carbons are enabled for this session
+    carbons: bool,
+    receipt_policy: ReceiptPolicy,
+    /// Messages awaiting a receipt, by id, with their recipient
+    pending_receipts: HashMap<String, PendingReceipt>,
+}
+
+/// The purpose of an IQ request awaiting its response
+enum PendingIqKind {
+    Bind,
+    Session,
+    RosterGet,
+    RosterSet,
+    DiscoInfo,
+    /// A disco#items query, possibly one page of several
+    DiscoItems(Option<DiscoPaging>),
+    /// Verification of the capabilities at the given node
+    Caps(String),
+    Ping,
+    Command,
+    /// Enabling (`true`) or disabling carbons
+    Carbons(bool),
+    /// An archive query with the given query id
+    Mam(String),
+    Muc(MucRequest),
+    MucSelfPing,
+}
+
+struct PendingIq {
+    to: Option<String>,
+    kind: PendingIqKind,
 }
 
 pub struct XmppStream {
@@ -112,150 +290,443 @@ impl XmppStream {
                 domain: domain.to_string(),
                 closed: false,
                 socket: XmppSocket::NoSock,
+                state: NegotiationState::Disconnected,
+                features: None,
+                authenticated: false,
                 authenticator: None,
-                pending_bind_id: None,
+                jid: None,
+                default_lang: None,
+                next_id: 0,
+                pending_iqs: HashMap::new(),
+                events: VecDeque::new(),
+                roster: Roster::default(),
+                roster_store: None,
+                subscription_requests: BTreeSet::new(),
+                presences: PresenceTracker::default(),
+                disco_info: disco::default_info(),
+                caps_node: caps::DEFAULT_NODE.into(),
+                caps_store: Box::new(MemoryCapsStore::new()),
+                entity_caps: HashMap::new(),
+                pending_caps: HashMap::new(),
+                ping_interval: None,
+                ping_timeout: ping::DEFAULT_TIMEOUT,
+                last_received: Instant::now(),
+                pending_ping: None,
+                read_timeout: None,
+                whitespace_interval: None,
+                close_timeout: keepalive::DEFAULT_CLOSE_TIMEOUT,
+                closing_since: None,
+                last_sent: Instant::now(),
+                rooms: BTreeMap::new(),
+                muc_ping_interval: None,
+                commands: BTreeMap::new(),
+                command_sessions: HashMap::new(),
+                mam_queries: HashMap::new(),
+                carbons: false,
+                receipt_policy: ReceiptPolicy::Subscribed,
+                pending_receipts: HashMap::new(),
             },
         }
     }
 
+    /// Connects to the server, closing any previous connection.
     pub fn connect(&mut self) -> io::Result<()> {
         let stream = {
             let address = &self.handler.domain[..];
-            TcpStream::connect(&(address, 5222))?
+            TcpStream::connect((address, 5222))?
         };
         let stream_read = stream.try_clone()?;
 
-        self.handler.socket = XmppSocket::Tcp(BufReader::new(stream_read), stream);
+        self.handler.socket.shutdown();
+        self.handler.socket = XmppSocket::Tcp(StrReader::new(stream_read), stream);
+        // Anything still buffered belongs to the previous stream
+        self.parser = xml::Parser::new();
+        self.handler.closed = false;
+        self.handler.closing_since = None;
+        self.handler.state = NegotiationState::Connecting;
+        self.handler.features = None;
+        self.handler.authenticated = false;
+        self.handler.authenticator = None;
+        self.handler.jid = None;
+        self.handler.pending_iqs.clear();
+        self.handler.mam_queries.clear();
+        self.handler.carbons = false;
+        self.handler.pending_caps.clear();
+        self.handler.last_received = Instant::now();
+        self.handler.pending_ping = None;
+        self.handler.events.clear();
+        // The server redelivers unanswered requests on login
+        self.handler.subscription_requests.clear();
         self.handler.start_stream()
     }
 
+    /// Closes the session gracefully.
+    ///
+    /// Sends unavailable presence and closes our stream, then waits up to the
+    /// close timeout for the server to close its stream before shutting down
+    /// the connection. Stanzas arriving in the meantime are still processed,
+    /// e.g. roster pushes update `roster()`, but their events are discarded.
+    /// Stream Management is not supported, so there are no unacknowledged
+    /// stanzas to flush; everything else is flushed when it is sent.
+    pub fn disconnect(&mut self) -> io::Result<()> {
+        let mut result = Ok(());
+        if !self.handler.closed {
+            if self.handler.state == NegotiationState::Online {
+                let id = self.handler.next_id();
+                let unavailable = stanzas::Presence::new(PresenceType::Unavailable, id);
+                let _ = self.handler.send(unavailable);
+            }
+            result = self.handler.close_stream();
+            if result.is_ok() {
+                while !matches!(self.handle(), Event::StreamClosed) {}
+            }
+        }
+        self.handler.handle_disconnect();
+        result
+    }
+
+    /// The current stage of the stream negotiation
+    pub fn state(&self) -> NegotiationState {
+        self.handler.state
+    }
+
+    /// The presence of our contacts, as received during this session
+    pub fn presences(&self) -> &PresenceTracker {
+        &self.handler.presences
+    }
+
+    /// The default `xml:lang` of the server's stream
+    pub fn default_lang(&self) -> Option<&str> {
+        self.handler.default_lang.as_deref()
+    }
+
+    /// The features most recently advertised by the server, if any
+    pub fn features(&self) -> Option<&StreamFeatures> {
+        self.handler.features.as_ref()
+    }
+
+    /// Sends `data`, adding our capabilities to available presence and
+    /// tracking messages that request a receipt
     pub fn send<T: XmppSend>(&mut self, data: T) -> io::Result<()> {
+        if let Some(message) = data.as_message() {
+            self.handler.track_receipt(message);
+        }
+        if let Some(presence) = data.as_presence() {
+            let available = presence.stanza_type() == Some(PresenceType::Available);
+            if available && presence.get_payload::<Caps>().is_none() {
+                let mut presence = presence.clone();
+                self.handler.add_caps(&mut presence);
+                return self.handler.send(presence);
+            }
+        }
         self.handler.send(data)
     }
 
-    pub fn handle(&mut self) -> Event {
+    pub fn handle(&mut self) -> Event<'_> {
         let builder = &mut self.builder;
         let handler = &mut self.handler;
         loop {
-            let string = match handler.socket.read_str() {
-                Ok(s) => s,
-                Err(_) => return Event::StreamClosed,
-            };
-            self.parser.feed_str(&string);
-            for event in &mut self.parser {
-                match event {
-                    Ok(xml::Event::ElementStart(xml::StartTag {
-                        ref name,
-                        ns: Some(ref ns),
-                        ref prefix,
-                        ..
-                    })) if *name == "stream" && *ns == ns::STREAMS => {
-                        println!("In: Stream start");
-                        match *prefix {
-                            Some(ref prefix) => {
-                                *builder = xml::ElementBuilder::new();
-                                builder.set_default_ns(ns::JABBER_CLIENT.to_string());
-                                builder.define_prefix(prefix.clone(), ns::STREAMS.to_string());
-                            }
-                            None => {
-                                *builder = xml::ElementBuilder::new();
-                                builder.set_default_ns(ns::STREAMS.to_string());
+            if let Some(event) = handler.events.pop_front() {
+                return event;
+            }
+
+            let event = match self.parser.next() {
+                Some(event) => event,
+                None => {
+                    let timeout = handler.next_timeout();
+                    let string = match handler.socket.set_read_timeout(timeout) {
+                        Ok(()) => handler.socket.read_str(),
+                        Err(e) => Err(e),
+                    };
+                    let string = match string {
+                        Ok(s) => s,
+                        Err(ref e)
+                            if e.kind() == io::ErrorKind::WouldBlock
+                                || e.kind() == io::ErrorKind::TimedOut =>
+                        {
+                            if handler.handle_timers() {
+                                continue;
                             }
+                            handler.handle_disconnect();
+                            return Event::StreamClosed;
+                        }
+                        // Includes the server shutting down its side of the connection
+                        Err(_) => {
+                            handler.handle_disconnect();
+                            return Event::StreamClosed;
+                        }
+                    };
+                    handler.last_received = Instant::now();
+                    self.parser.feed_str(&string);
+                    continue;
+                }
+            };
+
+            match event {
+                Ok(xml::Event::ElementStart(xml::StartTag {
+                    ref name,
+                    ns: Some(ref ns),
+                    ref prefix,
+                    ref attributes,
+                })) if *name == "stream" && *ns == ns::STREAMS => {
+                    println!("In: Stream start");
+                    handler.default_lang = attributes
+                        .get(&("lang".into(), Some(ns::XML.into())))
+                        .cloned();
+                    match *prefix {
+                        Some(ref prefix) => {
+                            *builder = xml::ElementBuilder::new();
+                            builder.set_default_ns(ns::JABBER_CLIENT.to_string());
+                            builder.define_prefix(prefix.clone(), ns::STREAMS.to_string());
+                        }
+                        None => {
+                            *builder = xml::ElementBuilder::new();
+                            builder.set_default_ns(ns::STREAMS.to_string());
                         }
                     }
-                    Ok(xml::Event::ElementEnd(xml::EndTag {
-                        ref name,
-                        ns: Some(ref ns),
-                        ..
-                    })) if *name == "stream" && *ns == ns::STREAMS => {
-                        println!("In: Stream end");
-                        let _ = handler.close_stream();
-                        return Event::StreamClosed;
-                    }
-                    event => match builder.handle_event(event) {
-                        None => (),
-                        Some(Ok(e)) => {
-                            println!("In: {}", e);
-                            let stanza = match stanzas::AStanza::from_element(e) {
-                                Ok(s) => s,
-                                Err(e) => {
-                                    // For IO errors we should return StreamClosed
-                                    // in the next iteration
-                                    let _ = handler.handle_non_stanza(e);
-                                    continue;
+                }
+                Ok(xml::Event::ElementEnd(xml::EndTag {
+                    ref name,
+                    ns: Some(ref ns),
+                    ..
+                })) if *name == "stream" && *ns == ns::STREAMS => {
+                    println!("In: Stream end");
+                    let _ = handler.close_stream();
+                    handler.handle_disconnect();
+                    return Event::StreamClosed;
+                }
+                event => match builder.handle_event(event) {
+                    None => (),
+                    Some(Ok(mut e)) => {
+                        println!("In: {}", e);
+                        // Make the stream's default language explicit
+                        if let Some(ref lang) = handler.default_lang {
+                            if e.get_attribute("lang", Some(ns::XML)).is_none() {
+                                e.set_attribute("lang".into(), Some(ns::XML.into()), lang.clone());
+                            }
+                        }
+                        let stanza = match stanzas::AStanza::from_element(e) {
+                            Ok(s) => s,
+                            Err(e) => {
+                                // For IO errors we should return StreamClosed
+                                // in the next iteration
+                                let _ = handler.handle_non_stanza(e);
+                                continue;
+                            }
+                        };
+                        match stanza {
+                            AStanza::MessageStanza(msg) => {
+                                let msg = match handler.handle_mam_result(msg) {
+                                    Some(msg) => msg,
+                                    None => continue,
+                                };
+                                let msg = match handler.handle_carbon(msg) {
+                                    Some(msg) => msg,
+                                    None => continue,
+                                };
+                                let msg = match handler.handle_receipts(msg) {
+                                    Some(msg) => msg,
+                                    None => continue,
+                                };
+                                if let Some(msg) = handler.handle_muc_message(msg) {
+                                    return Event::Message(msg);
                                 }
-                            };
-                            match stanza {
-                                AStanza::MessageStanza(msg) => return Event::Message(msg),
-                                AStanza::PresenceStanza(pres) => return Event::Presence(pres),
-                                AStanza::IqStanza(iq) => match iq.stanza_type() {
+                            }
+                            AStanza::PresenceStanza(pres) => {
+                                let pres = match handler.handle_muc_presence(pres) {
+                                    Some(pres) => pres,
                                     None => continue,
-                                    Some(IqType::Result)
-                                        if handler.pending_bind_id.as_deref() == iq.id() =>
-                                    {
-                                        handler.pending_bind_id = None;
-                                        return Event::Bound(iq.get_xmpp_bind_jid());
-                                    }
-                                    Some(IqType::Error)
-                                        if handler.pending_bind_id.as_deref() == iq.id() =>
-                                    {
-                                        handler.pending_bind_id = None;
-                                        return Event::BindError(iq);
-                                    }
-                                    Some(IqType::Result) | Some(IqType::Error) => {
-                                        return Event::IqResponse(iq)
+                                };
+                                if let Some(pres) = handler.handle_subscription(pres) {
+                                    handler.presences.update(&pres);
+                                    handler.handle_caps(&pres);
+                                    return Event::Presence(pres);
+                                }
+                            }
+                            AStanza::IqStanza(iq) => match iq.stanza_type() {
+                                None => (),
+                                Some(IqType::Result) | Some(IqType::Error) => {
+                                    if let Some(iq) = handler.handle_iq_response(iq) {
+                                        return Event::IqResponse(iq);
                                     }
-                                    Some(IqType::Set) | Some(IqType::Get) => {
+                                }
+                                Some(IqType::Set) | Some(IqType::Get) => {
+                                    if let Some(iq) = handler.handle_iq_request(iq) {
                                         return Event::IqRequest(IqGuard {
                                             iq,
                                             responded: false,
                                             handler,
-                                        })
+                                        });
                                     }
-                                },
-                            }
-                        }
-                        Some(Err(e)) => {
-                            println!("{}", e);
-                            let _ = handler.send(StreamError {
-                                cond: DefinedCondition::InvalidXml,
-                                text: None,
-                            });
-                            let _ = handler.close_stream();
-                            // Wait for remote to close stream
-                            // TODO: Avoid waiting forever
-                            continue;
+                                }
+                            },
                         }
-                    },
-                }
+                    }
+                    Some(Err(e)) => {
+                        println!("{}", e);
+                        let _ = handler.send(StreamError {
+                            cond: DefinedCondition::InvalidXml,
+                            text: None,
+                        });
+                        let _ = handler.close_stream();
+                        // Wait for remote to close stream, at most for the close timeout
+                    }
+                },
             }
         }
     }
 }
 
+impl Drop for XmppStream {
+    /// Closes the stream without waiting for the server
+    fn drop(&mut self) {
+        if let XmppSocket::NoSock = self.handler.socket {
+            return;
+        }
+        let _ = self.handler.close_stream();
+        self.handler.socket.shutdown();
+    }
+}
+
 impl XmppHandler {
     fn start_stream(&mut self) -> io::Result<()> {
         let stream_start = StreamStart { to: &self.domain };
         println!("Out: {}", stream_start);
+        self.last_sent = Instant::now();
         stream_start.xmpp_send(&mut self.socket)?;
         self.socket.flush()
     }
 
     fn close_stream(&mut self) -> io::Result<()> {
         if !self.closed {
+            let result = self.send(StreamEnd);
             self.closed = true;
-            self.send(StreamEnd)
+            self.closing_since = Some(Instant::now());
+            result
         } else {
             Ok(())
         }
     }
 
+    /// Shuts the connection down and forgets state that is only valid while connected
+    fn handle_disconnect(&mut self) {
+        self.socket.shutdown();
+        self.closed = true;
+        self.closing_since = None;
+        self.pending_ping = None;
+        self.state = NegotiationState::Disconnected;
+        self.presences.clear();
+        self.entity_caps.clear();
+        self.reset_rooms();
+        self.command_sessions.clear();
+        self.pending_receipts.clear();
+    }
+
     fn send<T: XmppSend>(&mut self, data: T) -> io::Result<()> {
+        // Nothing may follow our </stream:stream>
+        if self.closed {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "Stream already closed",
+            ));
+        }
         println!("Out: {}", data);
+        self.last_sent = Instant::now();
         data.xmpp_send(&mut self.socket)?;
         self.socket.flush()
     }
 
+    fn next_id(&mut self) -> String {
+        self.next_id += 1;
+        format!("rust-xmpp-{}", self.next_id)
+    }
+
+    /// Sends an IQ request with a fresh id and remembers it until the response arrives
+    fn send_iq(&mut self, mut iq: stanzas::Iq, kind: PendingIqKind) -> io::Result<String> {
+        let id = self.next_id();
+        iq.set_id(Some(id.clone()));
+        let to = iq.to().map(|x| x.into());
+        self.send(&iq)?;
+        self.pending_iqs.insert(id.clone(), PendingIq { to, kind });
+        Ok(id)
+    }
+
+    /// Whether `from` is a valid sender for a response to a request sent to `to`
+    fn is_response_from(&self, to: Option<&str>, from: Option<&str>) -> bool {
+        if to == from {
+            return true;
+        }
+        // Requests without 'to' are handled by our account
+        match (to, from, self.jid.as_deref()) {
+            (None, Some(from), Some(jid)) => {
+                from == jid || from == jid::bare(jid) || from == jid::domain(jid)
+            }
+            // Before binding, e.g. for the bind and session requests
+            (None, Some(from), None) => {
+                from == self.domain || from.split_once('@') == Some((&self.username, &self.domain))
+            }
+            _ => false,
+        }
+    }
+
+    /// Handles responses to our own requests, returns all other responses
+    fn handle_iq_response(&mut self, iq: stanzas::Iq) -> Option<stanzas::Iq> {
+        let pending = match iq.id().and_then(|id| self.pending_iqs.get(id)) {
+            Some(pending) if self.is_response_from(pending.to.as_deref(), iq.from()) => {
+                self.pending_iqs.remove(iq.id().unwrap()).unwrap()
+            }
+            _ => return Some(iq),
+        };
+
+        match pending.kind {
+            PendingIqKind::Bind => self.handle_bind_result(iq),
+            PendingIqKind::Session => self.handle_session_result(iq),
+            PendingIqKind::RosterGet => self.handle_roster_result(iq),
+            PendingIqKind::RosterSet => {
+                let id = iq.id().unwrap().into();
+                self.events
+                    .push_back(Event::RosterResult(id, iq_result(&iq)));
+            }
+            PendingIqKind::DiscoInfo => {
+                self.handle_disco_info_result(pending.to.unwrap_or_default(), iq)
+            }
+            PendingIqKind::DiscoItems(paging) => {
+                self.handle_disco_items_result(pending.to.unwrap_or_default(), paging, iq)
+            }
+            PendingIqKind::Caps(node) => self.handle_caps_result(node, iq),
+            PendingIqKind::Ping => self.handle_ping_result(),
+            PendingIqKind::MucSelfPing => {
+                self.handle_muc_self_ping_result(pending.to.unwrap_or_default(), iq)
+            }
+            PendingIqKind::Command => {
+                self.handle_command_result(pending.to.unwrap_or_default(), iq)
+            }
+            PendingIqKind::Carbons(enabled) => self.handle_carbons_result(enabled, iq),
+            PendingIqKind::Mam(query_id) => self.handle_mam_fin(query_id, iq),
+            PendingIqKind::Muc(request) => {
+                self.handle_muc_result(request, pending.to.unwrap_or_default(), iq)
+            }
+        }
+        None
+    }
+
+    /// Handles requests the library can answer itself, returns all others
+    fn handle_iq_request(&mut self, iq: stanzas::Iq) -> Option<stanzas::Iq> {
+        let (name, ns) = match iq.payload() {
+            Some(payload) => (payload.name.clone(), payload.ns.clone().unwrap_or_default()),
+            None => return Some(iq),
+        };
+        match (iq.stanza_type(), &name[..], &ns[..]) {
+            (Some(IqType::Set), "query", ns::ROSTER) => self.handle_roster_push(iq),
+            (Some(IqType::Get), "query", ns::DISCO_INFO) => self.handle_disco_info_request(iq),
+            (Some(IqType::Get), "query", ns::DISCO_ITEMS) => self.handle_disco_items_request(iq),
+            (Some(IqType::Get), "ping", ns::PING) => self.handle_ping_request(iq),
+            (Some(IqType::Set), "command", ns::COMMANDS) => self.handle_command_request(iq),
+            _ => return Some(iq),
+        }
+        None
+    }
+
     fn handle_non_stanza(&mut self, stanza: xml::Element) -> io::Result<()> {
         match stanza.ns.as_ref().map(|x| &x[..]) {
             Some(ns::STREAMS) if stanza.name == "features" => self.handle_features(stanza),
@@ -266,25 +737,21 @@ impl XmppHandler {
     }
 
     fn handle_features(&mut self, features: xml::Element) -> io::Result<()> {
-        // StartTLS
-        if features
-            .get_child("starttls", Some(ns::FEATURE_TLS))
-            .is_some()
-        {
-            return self.send(StartTls);
-        }
-
-        // Auth mechanisms
-        if let Some(mechs) = features.get_child("mechanisms", Some(ns::FEATURE_SASL)) {
-            return self.handle_mechs(mechs);
-        }
-
-        // Bind
-        if features.get_child("bind", Some(ns::FEATURE_BIND)).is_some() {
-            return self.handle_bind();
-        }
-
-        Ok(())
+        let features = StreamFeatures::from_element(&features);
+        let result = if features.starttls.is_some() && !self.socket.is_tls() {
+            self.state = NegotiationState::TlsNegotiating;
+            self.send(StartTls)
+        } else if !self.authenticated && !features.mechanisms.is_empty() {
+            self.state = NegotiationState::Authenticating;
+            self.handle_mechs(&features.mechanisms)
+        } else if self.authenticated && features.bind {
+            self.state = NegotiationState::Binding;
+            self.handle_bind()
+        } else {
+            Ok(())
+        };
+        self.features = Some(features);
+        result
     }
 
     fn handle_starttls(&mut self, starttls: xml::Element) -> io::Result<()> {
@@ -295,11 +762,8 @@ impl XmppHandler {
         Ok(())
     }
 
-    fn handle_mechs(&mut self, mechs: &xml::Element) -> io::Result<()> {
-        let mechs = mechs.get_children("mechanism", Some(ns::FEATURE_SASL));
-
+    fn handle_mechs(&mut self, mechs: &[String]) -> io::Result<()> {
         for mech in mechs {
-            let mech = mech.content_str();
             let mut auth: Box<dyn Authenticator> = match &mech[..] {
                 "SCRAM-SHA-1" => Box::new(ScramAuth::new(
                     self.username.clone(),
@@ -321,7 +785,7 @@ impl XmppHandler {
             self.authenticator = Some(auth);
 
             return self.send(AuthStart {
-                mech: &mech,
+                mech,
                 data: &initial,
             });
         }
@@ -366,6 +830,7 @@ impl XmppHandler {
                     }
                 }
             }
+            self.authenticated = true;
             return self.start_stream();
         }
 
@@ -373,15 +838,83 @@ impl XmppHandler {
     }
 
     fn handle_bind(&mut self) -> io::Result<()> {
-        let id: String = "bind".into();
+        let mut bind_iq = stanzas::Iq::new(stanzas::IqType::Set, String::new());
+        bind_iq.add_payload(stanzas::Bind::default());
+        self.send_iq(bind_iq, PendingIqKind::Bind).map(|_| ())
+    }
 
-        let mut bind_iq = stanzas::Iq::new(stanzas::IqType::Set, id.clone());
-        bind_iq.tag(xml::Element::new(
-            "bind".into(),
-            Some(ns::FEATURE_BIND.into()),
+    fn handle_bind_result(&mut self, iq: stanzas::Iq) {
+        if let Some(IqType::Error) = iq.stanza_type() {
+            self.events.push_back(Event::BindError(iq));
+            return;
+        }
+
+        self.jid = iq.get_xmpp_bind_jid();
+        if self.session_required() {
+            let _ = self.handle_session();
+            return;
+        }
+        self.state = NegotiationState::Online;
+        self.events.push_back(Event::Bound(self.jid.clone()));
+        self.rejoin_rooms();
+    }
+
+    fn session_required(&self) -> bool {
+        match self.features {
+            Some(StreamFeatures {
+                session: Some(ref session),
+                ..
+            }) => !session.optional,
+            _ => false,
+        }
+    }
+
+    fn handle_session(&mut self) -> io::Result<()> {
+        let mut session_iq = stanzas::Iq::new(stanzas::IqType::Set, String::new());
+        session_iq.tag(xml::Element::new(
+            "session".into(),
+            Some(ns::FEATURE_SESSION.into()),
             vec![],
         ));
-        self.pending_bind_id = Some(id);
-        self.send(bind_iq)
+        self.send_iq(session_iq, PendingIqKind::Session).map(|_| ())
+    }
+
+    fn handle_session_result(&mut self, iq: stanzas::Iq) {
+        if let Some(IqType::Error) = iq.stanza_type() {
+            self.events.push_back(Event::SessionError(iq));
+            return;
+        }
+
+        self.state = NegotiationState::Online;
+        self.events.push_back(Event::Bound(self.jid.clone()));
+        self.rejoin_rooms();
     }
 }
+
+/// `Ok` for IQ results, the stanza error for IQ errors
+#[allow(clippy::result_large_err)]
+fn iq_result(iq: &stanzas::Iq) -> Result<(), StanzaError> {
+    match iq.stanza_type() {
+        Some(IqType::Error) => Err(iq_error(iq)),
+        _ => Ok(()),
+    }
+}
+
+/// The error reported for responses we could not parse
+fn malformed() -> StanzaError {
+    StanzaError::new(
+        stanzas::ErrorType::Modify,
+        stanzas::DefinedCondition::UndefinedCondition,
+    )
+}
+
+/// The error of an IQ response, falling back to `undefined-condition`
+/// if the error is missing or malformed
+fn iq_error(iq: &stanzas::Iq) -> StanzaError {
+    iq.error().unwrap_or_else(|| {
+        StanzaError::new(
+            stanzas::ErrorType::Cancel,
+            stanzas::DefinedCondition::UndefinedCondition,
+        )
+    })
+}
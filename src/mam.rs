@@ -0,0 +1,252 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! Message Archive Management (XEP-0313)
+
+use std::io;
+use std::time::SystemTime;
+
+use crate::data_forms::{DataForm, Field, FieldType, FormType};
+use crate::date_time;
+use crate::ns;
+use crate::stanzas::{Forwarded, Iq, IqType, Message, Pager, Payload, ResultSet, Stanza};
+use crate::{iq_error, malformed, Event, PendingIqKind, XmppHandler, XmppStream};
+
+/// Filters and paging of an archive query
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MamQuery {
+    /// Only messages exchanged with this JID
+    pub with: Option<String>,
+    /// Only messages at or after this time
+    pub start: Option<SystemTime>,
+    /// Only messages at or before this time
+    pub end: Option<SystemTime>,
+    /// Only messages archived before the one with this id
+    pub before_id: Option<String>,
+    /// Only messages archived after the one with this id
+    pub after_id: Option<String>,
+    /// Selects the page of results
+    pub page: Option<ResultSet>,
+}
+
+impl MamQuery {
+    fn to_element(&self, query_id: &str) -> xml::Element {
+        let mut query = xml::Element::new(
+            "query".into(),
+            Some(ns::MAM.into()),
+            vec![("queryid".into(), None, query_id.into())],
+        );
+
+        let mut form = DataForm::new(FormType::Submit);
+        form.fields.push(Field {
+            ty: Some(FieldType::Hidden),
+            ..Field::with_value("FORM_TYPE", ns::MAM)
+        });
+        let filters = [
+            ("with", self.with.clone()),
+            ("start", self.start.map(date_time::format)),
+            ("end", self.end.map(date_time::format)),
+            ("before-id", self.before_id.clone()),
+            ("after-id", self.after_id.clone()),
+        ];
+        for (var, value) in filters.iter() {
+            if let Some(ref value) = *value {
+                form.fields.push(Field::with_value(var, value));
+            }
+        }
+        query.tag(form.to_element());
+
+        if let Some(ref page) = self.page {
+            query.tag(page.to_element());
+        }
+        query
+    }
+}
+
+/// The `<fin xmlns='urn:xmpp:mam:2'/>` payload ending the results of a query
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Fin {
+    /// The last page of the archive was returned
+    pub complete: bool,
+    /// The results are not subject to change, e.g. by messages still being archived
+    pub stable: bool,
+    /// Describes the returned page
+    pub set: ResultSet,
+}
+
+impl Payload for Fin {
+    const NAME: &'static str = "fin";
+    const NS: &'static str = ns::MAM;
+
+    fn try_from(elem: &xml::Element) -> Result<Fin, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a MAM fin");
+        }
+        let set = match elem.get_child(ResultSet::NAME, Some(ResultSet::NS)) {
+            Some(set) => <ResultSet as Payload>::try_from(set)?,
+            None => ResultSet::default(),
+        };
+        Ok(Fin {
+            complete: matches!(
+                elem.get_attribute("complete", None),
+                Some("true") | Some("1")
+            ),
+            stable: !matches!(
+                elem.get_attribute("stable", None),
+                Some("false") | Some("0")
+            ),
+            set,
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut attrs = vec![];
+        if self.complete {
+            attrs.push(("complete".into(), None, "true".into()));
+        }
+        if !self.stable {
+            attrs.push(("stable".into(), None, "false".into()));
+        }
+        let mut fin = xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), attrs);
+        fin.tag(self.set.to_element());
+        fin
+    }
+}
+
+/// An archive query in progress
+pub(crate) struct MamState {
+    /// `None` for our own archive
+    archive: Option<String>,
+    query: MamQuery,
+    /// Fetches further pages automatically
+    pager: Option<Pager>,
+    /// The number of results in the current page
+    received: usize,
+}
+
+impl XmppStream {
+    /// Queries the message archive of `archive`, e.g. a MUC room, or our own
+    /// archive if `None`.
+    ///
+    /// Returns the query id. Each archived message is reported by
+    /// `Event::ArchivedMessage`, followed by `Event::ArchiveQueryFinished`.
+    /// Only the page selected by `query.page` is returned, see `fetch_archive()`.
+    pub fn query_archive(&mut self, archive: Option<&str>, query: &MamQuery) -> io::Result<String> {
+        let query_id = self.handler.next_id();
+        self.handler
+            .send_mam_query(query_id.clone(), archive, query.clone(), None)?;
+        Ok(query_id)
+    }
+
+    /// Queries the message archive of `archive` page by page, as selected by `pager`.
+    ///
+    /// Returns the query id. The messages of all pages are reported by
+    /// `Event::ArchivedMessage`, followed by a single `Event::ArchiveQueryFinished`
+    /// for the last page.
+    pub fn fetch_archive(
+        &mut self,
+        archive: Option<&str>,
+        query: &MamQuery,
+        pager: Pager,
+    ) -> io::Result<String> {
+        let query_id = self.handler.next_id();
+        let query = MamQuery {
+            page: Some(pager.first()),
+            ..query.clone()
+        };
+        self.handler
+            .send_mam_query(query_id.clone(), archive, query, Some(pager))?;
+        Ok(query_id)
+    }
+}
+
+impl XmppHandler {
+    fn send_mam_query(
+        &mut self,
+        query_id: String,
+        archive: Option<&str>,
+        query: MamQuery,
+        pager: Option<Pager>,
+    ) -> io::Result<()> {
+        let mut iq = Iq::new(IqType::Set, String::new());
+        iq.set_to(archive.map(|x| x.into()));
+        iq.as_element_mut().tag(query.to_element(&query_id));
+        self.send_iq(iq, PendingIqKind::Mam(query_id.clone()))?;
+        let state = MamState {
+            archive: archive.map(|x| x.into()),
+            query,
+            pager,
+            received: 0,
+        };
+        self.mam_queries.insert(query_id, state);
+        Ok(())
+    }
+
+    pub(crate) fn handle_mam_fin(&mut self, query_id: String, iq: Iq) {
+        let state = self.mam_queries.remove(&query_id);
+        let result = match iq.stanza_type() {
+            Some(IqType::Error) => Err(iq_error(&iq)),
+            _ => iq.get_payload::<Fin>().ok_or_else(malformed),
+        };
+        if let (Some(state), Ok(fin)) = (state, &result) {
+            let next = match state.pager {
+                Some(mut pager) if !fin.complete => pager
+                    .next(Some(&fin.set), state.received)
+                    .map(|page| (page, pager)),
+                _ => None,
+            };
+            if let Some((page, pager)) = next {
+                let query = MamQuery {
+                    page: Some(page),
+                    ..state.query
+                };
+                // Reusing the query id keeps the pages together for the application
+                let archive = state.archive.as_deref();
+                let _ = self.send_mam_query(query_id, archive, query, Some(pager));
+                return;
+            }
+        }
+        self.events
+            .push_back(Event::ArchiveQueryFinished { query_id, result });
+    }
+
+    /// Unwraps results of our archive queries, returns all other messages
+    pub(crate) fn handle_mam_result(&mut self, message: Message) -> Option<Message> {
+        let result = match message.get_child("result", Some(ns::MAM)) {
+            Some(result) => result,
+            None => return Some(message),
+        };
+        let query_id = result.get_attribute("queryid", None).unwrap_or("");
+        let id = result.get_attribute("id", None).unwrap_or("");
+        // Only the queried archive may inject messages into the results. Our own
+        // archive is checked up front, as `state` borrows the handler mutably.
+        let from = message.from();
+        let from_own_archive = self.is_response_from(None, from);
+        let state = match self.mam_queries.get_mut(query_id) {
+            Some(state) if state.archive.is_none() && from_own_archive => state,
+            Some(state) if state.archive.is_some() && state.archive.as_deref() == from => state,
+            _ => return Some(message),
+        };
+        let forwarded = result
+            .get_child(Forwarded::NAME, Some(Forwarded::NS))
+            .and_then(|forwarded| <Forwarded as Payload>::try_from(forwarded).ok());
+        let stamp = forwarded
+            .as_ref()
+            .and_then(|forwarded| forwarded.delay.as_ref())
+            .map(|delay| delay.stamp);
+        // Malformed results are dropped
+        if let Some(archived) = forwarded.and_then(|forwarded| forwarded.into_message()) {
+            state.received += 1;
+            self.events.push_back(Event::ArchivedMessage {
+                query_id: query_id.into(),
+                id: id.into(),
+                stamp,
+                message: archived,
+            });
+        }
+        None
+    }
+}
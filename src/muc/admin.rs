@@ -0,0 +1,334 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! Moderation and administration of rooms (`muc#admin` and `muc#owner`)
+
+use std::io;
+
+use crate::data_forms::{DataForm, FormType};
+use crate::muc::{Affiliation, MucEvent, MucItem, Role};
+use crate::ns;
+use crate::stanzas::{Iq, IqType, Payload, Stanza};
+use crate::{iq_error, iq_result, malformed, Event, PendingIqKind, XmppHandler, XmppStream};
+
+/// The `<query xmlns='http://jabber.org/protocol/muc#admin'/>` payload
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MucAdmin {
+    pub items: Vec<MucItem>,
+}
+
+impl Payload for MucAdmin {
+    const NAME: &'static str = "query";
+    const NS: &'static str = ns::MUC_ADMIN;
+
+    fn try_from(elem: &xml::Element) -> Result<MucAdmin, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a MUC admin query");
+        }
+        let items = elem
+            .get_children("item", Some(Self::NS))
+            .map(|item| MucItem::from_element(item, Self::NS))
+            .collect::<Result<_, _>>()?;
+        Ok(MucAdmin { items })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut query = xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), vec![]);
+        for item in &self.items {
+            query.tag(item.to_element(Self::NS));
+        }
+        query
+    }
+}
+
+/// Destruction of a room, optionally pointing occupants to a replacement
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Destroy {
+    /// The JID of an alternate venue
+    pub jid: Option<String>,
+    pub reason: Option<String>,
+}
+
+/// The `<query xmlns='http://jabber.org/protocol/muc#owner'/>` payload
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MucOwner {
+    /// The room configuration
+    pub form: Option<DataForm>,
+    pub destroy: Option<Destroy>,
+}
+
+impl Payload for MucOwner {
+    const NAME: &'static str = "query";
+    const NS: &'static str = ns::MUC_OWNER;
+
+    fn try_from(elem: &xml::Element) -> Result<MucOwner, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a MUC owner query");
+        }
+        let destroy = elem
+            .get_child("destroy", Some(Self::NS))
+            .map(|destroy| Destroy {
+                jid: destroy.get_attribute("jid", None).map(|x| x.into()),
+                reason: destroy
+                    .get_child("reason", Some(Self::NS))
+                    .map(|reason| reason.content_str()),
+            });
+        Ok(MucOwner {
+            form: match elem.get_child("x", Some(ns::DATA_FORMS)) {
+                Some(form) => Some(<DataForm as Payload>::try_from(form)?),
+                None => None,
+            },
+            destroy,
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut query = xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), vec![]);
+        if let Some(ref form) = self.form {
+            query.tag(form.to_element());
+        }
+        if let Some(ref destroy) = self.destroy {
+            let attrs = destroy.jid.clone().map(|jid| ("jid".into(), None, jid));
+            let elem = query.tag(xml::Element::new(
+                "destroy".into(),
+                Some(Self::NS.into()),
+                attrs,
+            ));
+            if let Some(ref reason) = destroy.reason {
+                elem.tag(xml::Element::new(
+                    "reason".into(),
+                    Some(Self::NS.into()),
+                    vec![],
+                ))
+                .text(reason.clone());
+            }
+        }
+        query
+    }
+}
+
+/// The purpose of a pending admin or owner request
+pub(crate) enum MucRequest {
+    Change,
+    Items,
+    Config,
+}
+
+impl XmppStream {
+    /// Removes the occupant `nick` from `room`.
+    ///
+    /// Returns the id of the request, the outcome is reported by `MucEvent::AdminResult`.
+    pub fn kick(&mut self, room: &str, nick: &str, reason: Option<&str>) -> io::Result<String> {
+        self.set_role(room, nick, Role::None, reason)
+    }
+
+    /// Changes the role of the occupant `nick`, e.g. to grant or revoke voice
+    /// (`Role::Participant`/`Role::Visitor`) or moderator status.
+    ///
+    /// Returns the id of the request, the outcome is reported by `MucEvent::AdminResult`.
+    pub fn set_role(
+        &mut self,
+        room: &str,
+        nick: &str,
+        role: Role,
+        reason: Option<&str>,
+    ) -> io::Result<String> {
+        self.muc_admin_set(
+            room,
+            vec![MucItem {
+                role: Some(role),
+                nick: Some(nick.into()),
+                reason: reason.map(|x| x.into()),
+                ..MucItem::default()
+            }],
+        )
+    }
+
+    /// Bans the user `jid` from `room`.
+    ///
+    /// Returns the id of the request, the outcome is reported by `MucEvent::AdminResult`.
+    pub fn ban(&mut self, room: &str, jid: &str, reason: Option<&str>) -> io::Result<String> {
+        self.set_affiliation(room, jid, Affiliation::Outcast, reason)
+    }
+
+    /// Changes the affiliation of the user `jid`, e.g. to grant membership,
+    /// admin or owner status.
+    ///
+    /// Returns the id of the request, the outcome is reported by `MucEvent::AdminResult`.
+    pub fn set_affiliation(
+        &mut self,
+        room: &str,
+        jid: &str,
+        affiliation: Affiliation,
+        reason: Option<&str>,
+    ) -> io::Result<String> {
+        self.muc_admin_set(
+            room,
+            vec![MucItem {
+                affiliation: Some(affiliation),
+                jid: Some(jid.into()),
+                reason: reason.map(|x| x.into()),
+                ..MucItem::default()
+            }],
+        )
+    }
+
+    /// Changes several roles or affiliations at once, e.g. to edit a member list.
+    ///
+    /// Returns the id of the request, the outcome is reported by `MucEvent::AdminResult`.
+    pub fn set_muc_items(&mut self, room: &str, items: Vec<MucItem>) -> io::Result<String> {
+        self.muc_admin_set(room, items)
+    }
+
+    /// Requests the users with `affiliation`, e.g. the member or ban list.
+    ///
+    /// Returns the id of the request, the list is reported by `MucEvent::ItemList`.
+    pub fn fetch_affiliations(
+        &mut self,
+        room: &str,
+        affiliation: Affiliation,
+    ) -> io::Result<String> {
+        self.muc_admin_get(
+            room,
+            MucItem {
+                affiliation: Some(affiliation),
+                ..MucItem::default()
+            },
+        )
+    }
+
+    /// Requests the occupants with `role`, e.g. the moderator list.
+    ///
+    /// Returns the id of the request, the list is reported by `MucEvent::ItemList`.
+    pub fn fetch_roles(&mut self, room: &str, role: Role) -> io::Result<String> {
+        self.muc_admin_get(
+            room,
+            MucItem {
+                role: Some(role),
+                ..MucItem::default()
+            },
+        )
+    }
+
+    /// Requests the configuration form of `room`.
+    ///
+    /// Returns the id of the request, the form is reported by `MucEvent::RoomConfig`.
+    pub fn fetch_room_config(&mut self, room: &str) -> io::Result<String> {
+        let mut iq = Iq::new(IqType::Get, String::new());
+        iq.set_to(Some(room.into()));
+        iq.add_payload(MucOwner::default());
+        self.handler
+            .send_iq(iq, PendingIqKind::Muc(MucRequest::Config))
+    }
+
+    /// Submits the filled out configuration `form` of `room`, see `DataForm::to_submit()`.
+    ///
+    /// Returns the id of the request, the outcome is reported by `MucEvent::AdminResult`.
+    pub fn submit_room_config(&mut self, room: &str, form: DataForm) -> io::Result<String> {
+        self.muc_owner_set(
+            room,
+            MucOwner {
+                form: Some(form),
+                destroy: None,
+            },
+        )
+    }
+
+    /// Unlocks a newly created room with the default configuration.
+    ///
+    /// Returns the id of the request, the outcome is reported by `MucEvent::AdminResult`.
+    pub fn create_instant_room(&mut self, room: &str) -> io::Result<String> {
+        self.submit_room_config(room, DataForm::new(FormType::Submit))
+    }
+
+    /// Aborts the configuration of a newly created room, which destroys it.
+    ///
+    /// Returns the id of the request, the outcome is reported by `MucEvent::AdminResult`.
+    pub fn cancel_room_config(&mut self, room: &str) -> io::Result<String> {
+        self.submit_room_config(room, DataForm::new(FormType::Cancel))
+    }
+
+    /// Destroys `room`, optionally pointing its occupants to an `alternate` room.
+    ///
+    /// Returns the id of the request, the outcome is reported by `MucEvent::AdminResult`.
+    pub fn destroy_room(
+        &mut self,
+        room: &str,
+        alternate: Option<&str>,
+        reason: Option<&str>,
+    ) -> io::Result<String> {
+        self.muc_owner_set(
+            room,
+            MucOwner {
+                form: None,
+                destroy: Some(Destroy {
+                    jid: alternate.map(|x| x.into()),
+                    reason: reason.map(|x| x.into()),
+                }),
+            },
+        )
+    }
+
+    fn muc_admin_set(&mut self, room: &str, items: Vec<MucItem>) -> io::Result<String> {
+        let mut iq = Iq::new(IqType::Set, String::new());
+        iq.set_to(Some(room.into()));
+        iq.add_payload(MucAdmin { items });
+        self.handler
+            .send_iq(iq, PendingIqKind::Muc(MucRequest::Change))
+    }
+
+    fn muc_admin_get(&mut self, room: &str, filter: MucItem) -> io::Result<String> {
+        let mut iq = Iq::new(IqType::Get, String::new());
+        iq.set_to(Some(room.into()));
+        iq.add_payload(MucAdmin {
+            items: vec![filter],
+        });
+        self.handler
+            .send_iq(iq, PendingIqKind::Muc(MucRequest::Items))
+    }
+
+    fn muc_owner_set(&mut self, room: &str, query: MucOwner) -> io::Result<String> {
+        let mut iq = Iq::new(IqType::Set, String::new());
+        iq.set_to(Some(room.into()));
+        iq.add_payload(query);
+        self.handler
+            .send_iq(iq, PendingIqKind::Muc(MucRequest::Change))
+    }
+}
+
+impl XmppHandler {
+    pub(crate) fn handle_muc_result(&mut self, request: MucRequest, room: String, iq: Iq) {
+        let id = iq.id().unwrap().into();
+        let event = match request {
+            MucRequest::Change => MucEvent::AdminResult {
+                room,
+                id,
+                result: iq_result(&iq),
+            },
+            MucRequest::Items => {
+                let result = match iq.stanza_type() {
+                    Some(IqType::Error) => Err(iq_error(&iq)),
+                    _ => iq
+                        .get_payload::<MucAdmin>()
+                        .map(|query| query.items)
+                        .ok_or_else(malformed),
+                };
+                MucEvent::ItemList { room, id, result }
+            }
+            MucRequest::Config => {
+                let result = match iq.stanza_type() {
+                    Some(IqType::Error) => Err(iq_error(&iq)),
+                    _ => iq
+                        .get_payload::<MucOwner>()
+                        .and_then(|query| query.form)
+                        .ok_or_else(malformed),
+                };
+                MucEvent::RoomConfig { room, id, result }
+            }
+        };
+        self.events.push_back(Event::Muc(event));
+    }
+}
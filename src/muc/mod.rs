@@ -0,0 +1,808 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! Multi-User Chat (XEP-0045)
+
+use std::collections::BTreeMap;
+use std::io;
+use std::time::Instant;
+
+use crate::data_forms::DataForm;
+use crate::jid;
+use crate::ns;
+use crate::stanzas::{Message, MessageType, Payload, Presence, PresenceType, Stanza, StanzaError};
+use crate::{malformed, Event, XmppHandler, XmppStream};
+
+pub(crate) use self::admin::MucRequest;
+pub use self::admin::{Destroy, MucAdmin, MucOwner};
+
+mod admin;
+mod self_ping;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Role {
+    Moderator,
+    Participant,
+    Visitor,
+    None,
+}
+
+impl Role {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match *self {
+            Role::Moderator => "moderator",
+            Role::Participant => "participant",
+            Role::Visitor => "visitor",
+            Role::None => "none",
+        }
+    }
+
+    fn from_str(role: &str) -> Option<Role> {
+        match role {
+            "moderator" => Some(Role::Moderator),
+            "participant" => Some(Role::Participant),
+            "visitor" => Some(Role::Visitor),
+            "none" => Some(Role::None),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Affiliation {
+    Owner,
+    Admin,
+    Member,
+    /// Banned from the room
+    Outcast,
+    None,
+}
+
+impl Affiliation {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match *self {
+            Affiliation::Owner => "owner",
+            Affiliation::Admin => "admin",
+            Affiliation::Member => "member",
+            Affiliation::Outcast => "outcast",
+            Affiliation::None => "none",
+        }
+    }
+
+    fn from_str(affiliation: &str) -> Option<Affiliation> {
+        match affiliation {
+            "owner" => Some(Affiliation::Owner),
+            "admin" => Some(Affiliation::Admin),
+            "member" => Some(Affiliation::Member),
+            "outcast" => Some(Affiliation::Outcast),
+            "none" => Some(Affiliation::None),
+            _ => None,
+        }
+    }
+}
+
+/// Status codes sent with room presence
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// 100: Every occupant can see real JIDs
+    NonAnonymous,
+    /// 110: The presence is our own
+    SelfPresence,
+    /// 170: The room is logged
+    Logging,
+    /// 201: The room was created by joining it
+    RoomCreated,
+    /// 210: The service assigned or modified our nickname
+    NickAssigned,
+    /// 301: The occupant was banned
+    Banned,
+    /// 303: The occupant changed their nickname
+    NickChanged,
+    /// 307: The occupant was kicked
+    Kicked,
+    /// 321: The occupant was removed due to an affiliation change
+    AffiliationChanged,
+    /// 322: The occupant was removed because the room became members-only
+    MembersOnly,
+    /// 332: The occupant was removed because the service shut down
+    Shutdown,
+    Other(u16),
+}
+
+impl Status {
+    pub fn from_code(code: u16) -> Status {
+        match code {
+            100 => Status::NonAnonymous,
+            110 => Status::SelfPresence,
+            170 => Status::Logging,
+            201 => Status::RoomCreated,
+            210 => Status::NickAssigned,
+            301 => Status::Banned,
+            303 => Status::NickChanged,
+            307 => Status::Kicked,
+            321 => Status::AffiliationChanged,
+            322 => Status::MembersOnly,
+            332 => Status::Shutdown,
+            code => Status::Other(code),
+        }
+    }
+
+    pub fn code(&self) -> u16 {
+        match *self {
+            Status::NonAnonymous => 100,
+            Status::SelfPresence => 110,
+            Status::Logging => 170,
+            Status::RoomCreated => 201,
+            Status::NickAssigned => 210,
+            Status::Banned => 301,
+            Status::NickChanged => 303,
+            Status::Kicked => 307,
+            Status::AffiliationChanged => 321,
+            Status::MembersOnly => 322,
+            Status::Shutdown => 332,
+            Status::Other(code) => code,
+        }
+    }
+}
+
+/// How much discussion history to receive when joining a room
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct History {
+    pub max_chars: Option<u32>,
+    pub max_stanzas: Option<u32>,
+    pub seconds: Option<u32>,
+    /// Only messages after this XEP-0082 timestamp
+    pub since: Option<String>,
+}
+
+impl History {
+    fn to_element(&self) -> xml::Element {
+        let mut attrs = vec![];
+        if let Some(max_chars) = self.max_chars {
+            attrs.push(("maxchars".into(), None, max_chars.to_string()));
+        }
+        if let Some(max_stanzas) = self.max_stanzas {
+            attrs.push(("maxstanzas".into(), None, max_stanzas.to_string()));
+        }
+        if let Some(seconds) = self.seconds {
+            attrs.push(("seconds".into(), None, seconds.to_string()));
+        }
+        if let Some(ref since) = self.since {
+            attrs.push(("since".into(), None, since.clone()));
+        }
+        xml::Element::new("history".into(), Some(ns::MUC.into()), attrs)
+    }
+}
+
+/// The `<x xmlns='http://jabber.org/protocol/muc'/>` payload of a join request
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MucJoin {
+    pub password: Option<String>,
+    pub history: Option<History>,
+}
+
+impl Payload for MucJoin {
+    const NAME: &'static str = "x";
+    const NS: &'static str = ns::MUC;
+
+    fn try_from(elem: &xml::Element) -> Result<MucJoin, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a MUC join request");
+        }
+        let history = elem.get_child("history", Some(Self::NS)).map(|history| {
+            let attr = |name| {
+                history
+                    .get_attribute(name, None)
+                    .and_then(|x| x.parse().ok())
+            };
+            History {
+                max_chars: attr("maxchars"),
+                max_stanzas: attr("maxstanzas"),
+                seconds: attr("seconds"),
+                since: history.get_attribute("since", None).map(|x| x.into()),
+            }
+        });
+        Ok(MucJoin {
+            password: elem
+                .get_child("password", Some(Self::NS))
+                .map(|password| password.content_str()),
+            history,
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut x = xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), vec![]);
+        if let Some(ref password) = self.password {
+            x.tag(xml::Element::new(
+                "password".into(),
+                Some(Self::NS.into()),
+                vec![],
+            ))
+            .text(password.clone());
+        }
+        if let Some(ref history) = self.history {
+            x.tag(history.to_element());
+        }
+        x
+    }
+}
+
+/// An `<item/>` describing an occupant, as used by `muc#user` and `muc#admin`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MucItem {
+    pub affiliation: Option<Affiliation>,
+    pub role: Option<Role>,
+    pub jid: Option<String>,
+    pub nick: Option<String>,
+    pub reason: Option<String>,
+    /// The nickname of the occupant responsible for a change
+    pub actor: Option<String>,
+}
+
+impl MucItem {
+    pub(crate) fn from_element(item: &xml::Element, ns: &str) -> Result<MucItem, &'static str> {
+        let attr = |name| item.get_attribute(name, None).map(|x| x.to_string());
+        let affiliation = match item.get_attribute("affiliation", None) {
+            Some(affiliation) => {
+                Some(Affiliation::from_str(affiliation).ok_or("Invalid affiliation")?)
+            }
+            None => None,
+        };
+        let role = match item.get_attribute("role", None) {
+            Some(role) => Some(Role::from_str(role).ok_or("Invalid role")?),
+            None => None,
+        };
+        Ok(MucItem {
+            affiliation,
+            role,
+            jid: attr("jid"),
+            nick: attr("nick"),
+            reason: item
+                .get_child("reason", Some(ns))
+                .map(|reason| reason.content_str()),
+            actor: item
+                .get_child("actor", Some(ns))
+                .and_then(|actor| actor.get_attribute("nick", None))
+                .map(|x| x.into()),
+        })
+    }
+
+    pub(crate) fn to_element(&self, ns: &str) -> xml::Element {
+        let mut attrs = vec![];
+        if let Some(affiliation) = self.affiliation {
+            attrs.push(("affiliation".into(), None, affiliation.as_str().into()));
+        }
+        if let Some(role) = self.role {
+            attrs.push(("role".into(), None, role.as_str().into()));
+        }
+        if let Some(ref jid) = self.jid {
+            attrs.push(("jid".into(), None, jid.clone()));
+        }
+        if let Some(ref nick) = self.nick {
+            attrs.push(("nick".into(), None, nick.clone()));
+        }
+        let mut item = xml::Element::new("item".into(), Some(ns.into()), attrs);
+        if let Some(ref reason) = self.reason {
+            item.tag(xml::Element::new("reason".into(), Some(ns.into()), vec![]))
+                .text(reason.clone());
+        }
+        if let Some(ref actor) = self.actor {
+            item.tag(xml::Element::new(
+                "actor".into(),
+                Some(ns.into()),
+                vec![("nick".into(), None, actor.clone())],
+            ));
+        }
+        item
+    }
+}
+
+/// The `<x xmlns='http://jabber.org/protocol/muc#user'/>` payload of room presence
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MucUser {
+    pub items: Vec<MucItem>,
+    pub statuses: Vec<Status>,
+    /// The reason given when the room was destroyed
+    pub destroy_reason: Option<String>,
+}
+
+impl Payload for MucUser {
+    const NAME: &'static str = "x";
+    const NS: &'static str = ns::MUC_USER;
+
+    fn try_from(elem: &xml::Element) -> Result<MucUser, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a MUC user element");
+        }
+        let items = elem
+            .get_children("item", Some(Self::NS))
+            .map(|item| MucItem::from_element(item, Self::NS))
+            .collect::<Result<_, _>>()?;
+        let statuses = elem
+            .get_children("status", Some(Self::NS))
+            .filter_map(|status| status.get_attribute("code", None))
+            .filter_map(|code| code.parse().ok())
+            .map(Status::from_code)
+            .collect();
+        let destroy_reason = elem
+            .get_child("destroy", Some(Self::NS))
+            .and_then(|destroy| destroy.get_child("reason", Some(Self::NS)))
+            .map(|reason| reason.content_str());
+        Ok(MucUser {
+            items,
+            statuses,
+            destroy_reason,
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut x = xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), vec![]);
+        for item in &self.items {
+            x.tag(item.to_element(Self::NS));
+        }
+        for status in &self.statuses {
+            x.tag(xml::Element::new(
+                "status".into(),
+                Some(Self::NS.into()),
+                vec![("code".into(), None, status.code().to_string())],
+            ));
+        }
+        if let Some(ref reason) = self.destroy_reason {
+            x.tag(xml::Element::new(
+                "destroy".into(),
+                Some(Self::NS.into()),
+                vec![],
+            ))
+            .tag(xml::Element::new(
+                "reason".into(),
+                Some(Self::NS.into()),
+                vec![],
+            ))
+            .text(reason.clone());
+        }
+        x
+    }
+}
+
+/// A participant of a room
+#[derive(Clone)]
+pub struct Occupant {
+    pub nick: String,
+    /// The real JID, if the room makes it visible to us
+    pub jid: Option<String>,
+    pub role: Role,
+    pub affiliation: Affiliation,
+    /// The latest presence of the occupant
+    pub presence: Presence,
+}
+
+/// A room we joined, or are joining
+#[derive(Clone)]
+pub struct Room {
+    jid: String,
+    nick: String,
+    password: Option<String>,
+    history: Option<History>,
+    joined: bool,
+    /// We asked to leave, so the room must not be joined again on reconnect
+    leaving: bool,
+    subject: Option<String>,
+    occupants: BTreeMap<String, Occupant>,
+    /// When we last received a stanza from the room
+    last_activity: Instant,
+    /// When the unanswered self-ping was sent
+    ping_sent: Option<Instant>,
+}
+
+impl Room {
+    /// The bare JID of the room
+    pub fn jid(&self) -> &str {
+        &self.jid
+    }
+
+    /// Our own nickname in the room
+    pub fn nick(&self) -> &str {
+        &self.nick
+    }
+
+    /// Whether the room confirmed our presence
+    pub fn is_joined(&self) -> bool {
+        self.joined
+    }
+
+    pub fn subject(&self) -> Option<&str> {
+        self.subject.as_deref()
+    }
+
+    pub fn occupant(&self, nick: &str) -> Option<&Occupant> {
+        self.occupants.get(nick)
+    }
+
+    pub fn occupants(&self) -> impl Iterator<Item = &Occupant> {
+        self.occupants.values()
+    }
+}
+
+pub enum MucEvent {
+    /// We entered the room, see `statuses` e.g. for `Status::RoomCreated`.
+    ///
+    /// A newly created room stays locked until it is configured, see
+    /// `XmppStream::create_instant_room()` and `XmppStream::fetch_room_config()`.
+    Joined {
+        room: String,
+        nick: String,
+        statuses: Vec<Status>,
+    },
+    /// The room no longer knew us, so we are joining it again
+    Rejoining {
+        room: String,
+    },
+    /// The room refused to let us enter
+    JoinError {
+        room: String,
+        error: StanzaError,
+    },
+    /// The room reported an error after we joined, e.g. for a nickname change
+    Error {
+        room: String,
+        error: StanzaError,
+    },
+    /// We are no longer in the room, because we left, were removed, or it was destroyed
+    Left {
+        room: String,
+        statuses: Vec<Status>,
+        reason: Option<String>,
+    },
+    OccupantJoined {
+        room: String,
+        occupant: Occupant,
+    },
+    /// The role, affiliation or presence of an occupant changed
+    OccupantChanged {
+        room: String,
+        occupant: Occupant,
+    },
+    OccupantLeft {
+        room: String,
+        occupant: Occupant,
+        statuses: Vec<Status>,
+        reason: Option<String>,
+    },
+    NickChanged {
+        room: String,
+        old_nick: String,
+        new_nick: String,
+    },
+    /// A groupchat message, `nick` is `None` for messages from the room itself
+    Message {
+        room: String,
+        nick: Option<String>,
+        message: Message,
+    },
+    /// The subject was changed, or announced when joining
+    Subject {
+        room: String,
+        nick: Option<String>,
+        subject: Option<String>,
+    },
+    /// The outcome of a moderation or administration request
+    AdminResult {
+        room: String,
+        id: String,
+        result: Result<(), StanzaError>,
+    },
+    /// The response to a request for an affiliation or role list
+    ItemList {
+        room: String,
+        id: String,
+        result: Result<Vec<MucItem>, StanzaError>,
+    },
+    /// The room configuration form, to be submitted with `XmppStream::submit_room_config()`
+    RoomConfig {
+        room: String,
+        id: String,
+        result: Result<DataForm, StanzaError>,
+    },
+}
+
+fn room_error(room: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("Not in room {}", room))
+}
+
+impl XmppStream {
+    /// Joins `room` as `nick`.
+    ///
+    /// `Event::Muc(MucEvent::Joined)` is emitted after the room sent us all occupants,
+    /// `Event::Muc(MucEvent::JoinError)` if we were not allowed in.
+    pub fn join_room(
+        &mut self,
+        room: &str,
+        nick: &str,
+        password: Option<&str>,
+        history: Option<History>,
+    ) -> io::Result<()> {
+        let room = jid::bare(room).to_string();
+        self.handler.rooms.insert(
+            room.clone(),
+            Room {
+                jid: room.clone(),
+                nick: nick.into(),
+                password: password.map(|x| x.into()),
+                history,
+                joined: false,
+                leaving: false,
+                subject: None,
+                occupants: BTreeMap::new(),
+                last_activity: Instant::now(),
+                ping_sent: None,
+            },
+        );
+        self.handler.rejoin_room(&room)
+    }
+
+    /// Leaves `room`, `Event::Muc(MucEvent::Left)` is emitted once the room confirmed it
+    pub fn leave_room(&mut self, room: &str, status: Option<&str>) -> io::Result<()> {
+        let to = self.occupant_jid(room)?;
+        self.handler.rooms.get_mut(room).unwrap().leaving = true;
+        let mut presence = Presence::new(PresenceType::Unavailable, self.handler.next_id());
+        presence.set_to(Some(to));
+        presence.set_status(None, status.map(|x| x.into()));
+        self.send(presence)
+    }
+
+    /// Changes our nickname in `room`, reported by `MucEvent::NickChanged`
+    pub fn change_nick(&mut self, room: &str, nick: &str) -> io::Result<()> {
+        if !self.handler.rooms.contains_key(room) {
+            return Err(room_error(room));
+        }
+        let mut presence = Presence::new(PresenceType::Available, self.handler.next_id());
+        presence.set_to(Some(format!("{}/{}", room, nick)));
+        self.send(presence)
+    }
+
+    /// Sends `body` to all occupants of `room`, returns the id of the message
+    pub fn send_groupchat(&mut self, room: &str, body: &str) -> io::Result<String> {
+        let mut message = self.groupchat(room)?;
+        message.set_body(None, Some(body.into()));
+        let id = message.id().unwrap_or("").to_string();
+        self.send(message)?;
+        Ok(id)
+    }
+
+    /// Changes the subject of `room`, `None` clears it
+    pub fn set_room_subject(&mut self, room: &str, subject: Option<&str>) -> io::Result<()> {
+        let mut message = self.groupchat(room)?;
+        // An empty subject clears it
+        message.set_subject(None, Some(subject.unwrap_or("").into()));
+        self.send(message)
+    }
+
+    pub fn room(&self, room: &str) -> Option<&Room> {
+        self.handler.rooms.get(room)
+    }
+
+    /// The rooms we joined or are joining, they are joined again after reconnecting
+    pub fn rooms(&self) -> impl Iterator<Item = &Room> {
+        self.handler.rooms.values()
+    }
+
+    fn occupant_jid(&self, room: &str) -> io::Result<String> {
+        let room = self
+            .handler
+            .rooms
+            .get(room)
+            .ok_or_else(|| room_error(room))?;
+        Ok(format!("{}/{}", room.jid, room.nick))
+    }
+
+    fn groupchat(&mut self, room: &str) -> io::Result<Message> {
+        if !self.handler.rooms.contains_key(room) {
+            return Err(room_error(room));
+        }
+        let mut message = Message::new(MessageType::Groupchat, self.handler.next_id());
+        message.set_to(Some(room.into()));
+        Ok(message)
+    }
+}
+
+impl XmppHandler {
+    /// Turns presence from joined rooms into events, returns all others
+    pub(crate) fn handle_muc_presence(&mut self, presence: Presence) -> Option<Presence> {
+        let (room_jid, nick) = match presence.from() {
+            Some(from) if self.rooms.contains_key(jid::bare(from)) => (
+                jid::bare(from).to_string(),
+                jid::resource(from).map(|x| x.to_string()),
+            ),
+            _ => return Some(presence),
+        };
+        let room = self.rooms.get_mut(&room_jid).unwrap();
+        room.last_activity = Instant::now();
+
+        if let Some(PresenceType::Error) = presence.stanza_type() {
+            let error = presence.error().unwrap_or_else(malformed);
+            let event = if room.joined {
+                MucEvent::Error {
+                    room: room_jid,
+                    error,
+                }
+            } else {
+                self.rooms.remove(&room_jid);
+                MucEvent::JoinError {
+                    room: room_jid,
+                    error,
+                }
+            };
+            self.events.push_back(Event::Muc(event));
+            return None;
+        }
+
+        let nick = match nick {
+            Some(nick) => nick,
+            None => return Some(presence),
+        };
+        let user = presence.get_payload::<MucUser>().unwrap_or_default();
+        let item = user.items.into_iter().next().unwrap_or_default();
+        let statuses = user.statuses;
+        let is_self = statuses.contains(&Status::SelfPresence) || nick == room.nick;
+
+        let event = match presence.stanza_type() {
+            Some(PresenceType::Unavailable) => {
+                let occupant = room.occupants.remove(&nick);
+                match (statuses.contains(&Status::NickChanged), item.nick) {
+                    (true, Some(new_nick)) => {
+                        if let Some(mut occupant) = occupant {
+                            occupant.nick = new_nick.clone();
+                            room.occupants.insert(new_nick.clone(), occupant);
+                        }
+                        if is_self {
+                            room.nick = new_nick.clone();
+                        }
+                        MucEvent::NickChanged {
+                            room: room_jid,
+                            old_nick: nick,
+                            new_nick,
+                        }
+                    }
+                    _ if is_self => {
+                        self.rooms.remove(&room_jid);
+                        MucEvent::Left {
+                            room: room_jid,
+                            statuses,
+                            reason: item.reason.or(user.destroy_reason),
+                        }
+                    }
+                    _ => MucEvent::OccupantLeft {
+                        room: room_jid,
+                        occupant: occupant.unwrap_or(Occupant {
+                            nick,
+                            jid: item.jid,
+                            role: Role::None,
+                            affiliation: item.affiliation.unwrap_or(Affiliation::None),
+                            presence,
+                        }),
+                        statuses,
+                        reason: item.reason,
+                    },
+                }
+            }
+            Some(PresenceType::Available) => {
+                let occupant = Occupant {
+                    nick: nick.clone(),
+                    jid: item.jid,
+                    role: item.role.unwrap_or(Role::None),
+                    affiliation: item.affiliation.unwrap_or(Affiliation::None),
+                    presence,
+                };
+                let existed = room
+                    .occupants
+                    .insert(nick.clone(), occupant.clone())
+                    .is_some();
+                if is_self && !room.joined {
+                    // Our own presence comes last, after all other occupants
+                    room.joined = true;
+                    room.nick = nick.clone();
+                    MucEvent::Joined {
+                        room: room_jid,
+                        nick,
+                        statuses,
+                    }
+                } else if existed {
+                    MucEvent::OccupantChanged {
+                        room: room_jid,
+                        occupant,
+                    }
+                } else {
+                    MucEvent::OccupantJoined {
+                        room: room_jid,
+                        occupant,
+                    }
+                }
+            }
+            _ => return Some(presence),
+        };
+        self.events.push_back(Event::Muc(event));
+        None
+    }
+
+    /// Turns groupchat messages from joined rooms into events, returns all others
+    pub(crate) fn handle_muc_message(&mut self, message: Message) -> Option<Message> {
+        let room = match message.from() {
+            Some(from) if self.rooms.contains_key(jid::bare(from)) => jid::bare(from).to_string(),
+            _ => return Some(message),
+        };
+        self.rooms.get_mut(&room).unwrap().last_activity = Instant::now();
+        if !matches!(message.stanza_type(), Some(MessageType::Groupchat)) {
+            return Some(message);
+        }
+        let nick = message
+            .from()
+            .and_then(jid::resource)
+            .map(|x| x.to_string());
+
+        // A subject change is a message with a subject, but no body
+        let has_subject = message
+            .get_child("subject", Some(ns::JABBER_CLIENT))
+            .is_some();
+        let event = if has_subject && message.body(None).is_none() {
+            let subject = message.subject(None).filter(|subject| !subject.is_empty());
+            self.rooms.get_mut(&room).unwrap().subject = subject.clone();
+            MucEvent::Subject {
+                room,
+                nick,
+                subject,
+            }
+        } else {
+            MucEvent::Message {
+                room,
+                nick,
+                message,
+            }
+        };
+        self.events.push_back(Event::Muc(event));
+        None
+    }
+
+    /// Sends the presence to join `room`, with the nickname and password
+    /// used initially
+    pub(crate) fn rejoin_room(&mut self, room: &str) -> io::Result<()> {
+        let id = self.next_id();
+        let room = match self.rooms.get_mut(room) {
+            Some(room) => room,
+            None => return Ok(()),
+        };
+        room.joined = false;
+        room.occupants.clear();
+        room.ping_sent = None;
+        room.last_activity = Instant::now();
+
+        let mut presence = Presence::new(PresenceType::Available, id);
+        presence.set_to(Some(format!("{}/{}", room.jid, room.nick)));
+        presence.add_payload(MucJoin {
+            password: room.password.clone(),
+            history: room.history.clone(),
+        });
+        self.add_caps(&mut presence);
+        self.send(presence)
+    }
+
+    /// Joins all rooms again after reconnecting
+    pub(crate) fn rejoin_rooms(&mut self) {
+        let rooms: Vec<String> = self.rooms.keys().cloned().collect();
+        for room in rooms {
+            let _ = self.rejoin_room(&room);
+        }
+    }
+
+    /// Forgets the occupants of all rooms, and rooms we were leaving anyway
+    pub(crate) fn reset_rooms(&mut self) {
+        self.rooms.retain(|_, room| !room.leaving);
+        for room in self.rooms.values_mut() {
+            room.joined = false;
+            room.occupants.clear();
+            room.ping_sent = None;
+        }
+    }
+}
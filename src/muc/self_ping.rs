@@ -0,0 +1,98 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! MUC Self-Ping (XEP-0410)
+
+use std::time::{Duration, Instant};
+
+use crate::jid;
+use crate::muc::MucEvent;
+use crate::ping::Ping;
+use crate::stanzas::{DefinedCondition, Iq, IqType, Stanza};
+use crate::{Event, NegotiationState, PendingIqKind, XmppHandler, XmppStream};
+
+impl XmppStream {
+    /// Pings our own occupant JID after `interval` without traffic from a room,
+    /// `None` disables this.
+    ///
+    /// If the room no longer knows us, e.g. after a restart of the service,
+    /// it is joined again with the same nickname, see `MucEvent::Rejoining`.
+    pub fn set_muc_self_ping_interval(&mut self, interval: Option<Duration>) {
+        self.handler.muc_ping_interval = interval;
+    }
+}
+
+impl XmppHandler {
+    /// When the next self-ping is due or times out, if any
+    pub(crate) fn muc_ping_deadline(&self) -> Option<Instant> {
+        let interval = self.muc_ping_interval?;
+        if self.closed || self.state != NegotiationState::Online {
+            return None;
+        }
+        self.rooms
+            .values()
+            .filter(|room| room.joined)
+            .map(|room| match room.ping_sent {
+                Some(sent) => sent + self.ping_timeout,
+                None => room.last_activity + interval,
+            })
+            .min()
+    }
+
+    pub(crate) fn check_muc_self_pings(&mut self) {
+        let now = Instant::now();
+        let interval = match self.muc_ping_interval {
+            Some(interval) if !self.closed && self.state == NegotiationState::Online => interval,
+            _ => return,
+        };
+        let mut due = vec![];
+        for room in self.rooms.values_mut().filter(|room| room.joined) {
+            match room.ping_sent {
+                // An unanswered ping says nothing about the room, try again later
+                Some(sent) if sent + self.ping_timeout <= now => {
+                    room.ping_sent = None;
+                    room.last_activity = now;
+                }
+                None if room.last_activity + interval <= now => {
+                    room.ping_sent = Some(now);
+                    due.push(format!("{}/{}", room.jid, room.nick));
+                }
+                _ => (),
+            }
+        }
+        for to in due {
+            let mut iq = Iq::new(IqType::Get, String::new());
+            iq.set_to(Some(to));
+            iq.add_payload(Ping);
+            let _ = self.send_iq(iq, PendingIqKind::MucSelfPing);
+        }
+    }
+
+    pub(crate) fn handle_muc_self_ping_result(&mut self, to: String, iq: Iq) {
+        let room_jid = jid::bare(&to).to_string();
+        match self.rooms.get_mut(&room_jid) {
+            Some(room) => {
+                room.ping_sent = None;
+                room.last_activity = Instant::now();
+            }
+            None => return,
+        }
+
+        // Other errors, e.g. from services not supporting pings, still mean we are joined
+        let dropped = iq.error().is_some_and(|error| {
+            matches!(
+                error.condition,
+                DefinedCondition::NotAcceptable | DefinedCondition::ItemNotFound
+            )
+        });
+        if dropped {
+            self.events.push_back(Event::Muc(MucEvent::Rejoining {
+                room: room_jid.clone(),
+            }));
+            let _ = self.rejoin_room(&room_jid);
+        }
+    }
+}
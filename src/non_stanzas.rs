@@ -39,6 +39,17 @@ impl fmt::Display for StreamEnd {
 
 impl XmppSend for StreamEnd {}
 
+/// Whitespace between stanzas, used as a keepalive
+pub struct Whitespace;
+
+impl fmt::Display for Whitespace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, " ")
+    }
+}
+
+impl XmppSend for Whitespace {}
+
 #[derive(Debug)]
 pub struct StartTls;
 
@@ -7,10 +7,38 @@
 pub const JABBER_CLIENT: &str = "jabber:client";
 pub const JABBER_SERVER: &str = "jabber:server";
 pub const STREAMS: &str = "http://etherx.jabber.org/streams";
+pub const XML: &str = "http://www.w3.org/XML/1998/namespace";
 
 pub const FEATURE_BIND: &str = "urn:ietf:params:xml:ns:xmpp-bind";
+pub const FEATURE_CSI: &str = "urn:xmpp:csi:0";
+pub const FEATURE_PRE_APPROVAL: &str = "urn:xmpp:features:pre-approval";
+pub const FEATURE_REGISTER: &str = "http://jabber.org/features/iq-register";
+pub const FEATURE_ROSTER_VER: &str = "urn:xmpp:features:rosterver";
 pub const FEATURE_SASL: &str = "urn:ietf:params:xml:ns:xmpp-sasl";
+pub const FEATURE_SESSION: &str = "urn:ietf:params:xml:ns:xmpp-session";
+pub const FEATURE_SM: &str = "urn:xmpp:sm:3";
 pub const FEATURE_TLS: &str = "urn:ietf:params:xml:ns:xmpp-tls";
 
+pub const CARBONS: &str = "urn:xmpp:carbons:2";
+pub const CAPS: &str = "http://jabber.org/protocol/caps";
+pub const CAPS2: &str = "urn:xmpp:caps";
+pub const COMMANDS: &str = "http://jabber.org/protocol/commands";
+pub const DATA_FORMS: &str = "jabber:x:data";
+pub const DELAY: &str = "urn:xmpp:delay";
+pub const DISCO_INFO: &str = "http://jabber.org/protocol/disco#info";
+pub const DISCO_ITEMS: &str = "http://jabber.org/protocol/disco#items";
+pub const FORWARD: &str = "urn:xmpp:forward:0";
+pub const HASHES: &str = "urn:xmpp:hashes:2";
+pub const HINTS: &str = "urn:xmpp:hints";
+pub const MAM: &str = "urn:xmpp:mam:2";
+pub const MUC: &str = "http://jabber.org/protocol/muc";
+pub const MUC_ADMIN: &str = "http://jabber.org/protocol/muc#admin";
+pub const MUC_OWNER: &str = "http://jabber.org/protocol/muc#owner";
+pub const MUC_USER: &str = "http://jabber.org/protocol/muc#user";
+pub const PING: &str = "urn:xmpp:ping";
+pub const RECEIPTS: &str = "urn:xmpp:receipts";
+pub const ROSTER: &str = "jabber:iq:roster";
+pub const RSM: &str = "http://jabber.org/protocol/rsm";
+
 pub const STANZA_ERRORS: &str = "urn:ietf:params:xml:ns:xmpp-stanzas";
 pub const STREAM_ERRORS: &str = "urn:ietf:params:xml:ns:xmpp-streams";
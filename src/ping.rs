@@ -0,0 +1,93 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! XMPP Ping (XEP-0199)
+
+use std::time::{Duration, Instant};
+
+use crate::ns;
+use crate::stanzas::{Iq, IqType, Payload, Stanza};
+use crate::{NegotiationState, PendingIqKind, XmppHandler, XmppStream};
+
+/// How long to wait for a pong by default
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The `<ping xmlns='urn:xmpp:ping'/>` payload
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Ping;
+
+impl Payload for Ping {
+    const NAME: &'static str = "ping";
+    const NS: &'static str = ns::PING;
+
+    fn try_from(elem: &xml::Element) -> Result<Ping, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a ping");
+        }
+        Ok(Ping)
+    }
+
+    fn to_element(&self) -> xml::Element {
+        xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), vec![])
+    }
+}
+
+impl XmppStream {
+    /// Pings the server after `interval` without incoming traffic, `None` disables this.
+    ///
+    /// If no pong arrives within the ping timeout the connection is considered
+    /// dead, and `Event::StreamClosed` is emitted.
+    pub fn set_ping_interval(&mut self, interval: Option<Duration>) {
+        self.handler.ping_interval = interval;
+    }
+
+    /// Sets how long to wait for a pong, by default `DEFAULT_TIMEOUT`
+    pub fn set_ping_timeout(&mut self, timeout: Duration) {
+        self.handler.ping_timeout = timeout;
+    }
+}
+
+impl XmppHandler {
+    /// When the ping timer expires next, if it is running
+    pub(crate) fn ping_deadline(&self) -> Option<Instant> {
+        match self.pending_ping {
+            Some(sent) => Some(sent + self.ping_timeout),
+            None if self.state == NegotiationState::Online && !self.closed => self
+                .ping_interval
+                .map(|interval| self.last_received + interval),
+            None => None,
+        }
+    }
+
+    /// Sends a ping when idle, returns `false` if the last one went unanswered
+    pub(crate) fn check_ping(&mut self) -> bool {
+        match self.ping_deadline() {
+            Some(deadline) if deadline <= Instant::now() => (),
+            _ => return true,
+        }
+        if self.pending_ping.is_some() {
+            return false;
+        }
+
+        let mut iq = Iq::new(IqType::Get, String::new());
+        iq.set_to(Some(self.domain.clone()));
+        iq.add_payload(Ping);
+        if self.send_iq(iq, PendingIqKind::Ping).is_err() {
+            return false;
+        }
+        self.pending_ping = Some(Instant::now());
+        true
+    }
+
+    /// Any response, even an error, shows the connection is alive
+    pub(crate) fn handle_ping_result(&mut self) {
+        self.pending_ping = None;
+    }
+
+    pub(crate) fn handle_ping_request(&mut self, iq: Iq) {
+        let _ = self.send(iq.result_reply());
+    }
+}
@@ -0,0 +1,111 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! Tracking of the current presence of contacts and their resources
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::jid;
+use crate::stanzas::{Presence, PresenceType, Show, Stanza};
+
+/// The latest available presence of every resource we received presence from
+#[derive(Default)]
+pub struct PresenceTracker {
+    /// bare JID -> resource -> (sequence number, presence)
+    contacts: HashMap<String, BTreeMap<String, (u64, Presence)>>,
+    seq: u64,
+}
+
+/// Ranks availability, more available is higher
+fn show_rank(show: Option<Show>) -> u8 {
+    match show {
+        Some(Show::Chat) => 4,
+        None => 3,
+        Some(Show::Away) => 2,
+        Some(Show::Xa) => 1,
+        Some(Show::Dnd) => 0,
+    }
+}
+
+impl PresenceTracker {
+    /// The presence of the resource `full_jid`, if it is available
+    pub fn get(&self, full_jid: &str) -> Option<&Presence> {
+        let resource = jid::resource(full_jid).unwrap_or("");
+        self.contacts
+            .get(jid::bare(full_jid))
+            .and_then(|resources| resources.get(resource))
+            .map(|(_, presence)| presence)
+    }
+
+    /// Whether any resource of `jid` is available
+    pub fn is_available(&self, jid: &str) -> bool {
+        self.contacts.contains_key(jid::bare(jid))
+    }
+
+    /// All available resources of `jid` and their presence
+    pub fn resources<'a>(&'a self, jid: &str) -> impl Iterator<Item = (&'a str, &'a Presence)> {
+        self.contacts
+            .get(jid::bare(jid))
+            .into_iter()
+            .flat_map(|resources| resources.iter())
+            .map(|(resource, (_, presence))| (&resource[..], presence))
+    }
+
+    /// The resource of `jid` best suited to receive messages.
+    ///
+    /// Resources are ranked by priority, then by their `<show/>` value, then
+    /// by how recent their presence is. Resources with negative priority never
+    /// receive messages sent to the bare JID (RFC 6121 §8.5.2.1.1), so they are
+    /// not considered.
+    pub fn best_resource<'a>(&'a self, jid: &str) -> Option<(&'a str, &'a Presence)> {
+        self.contacts
+            .get(jid::bare(jid))?
+            .iter()
+            .filter(|(_, (_, presence))| presence.priority() >= 0)
+            .max_by_key(|(_, (seq, presence))| {
+                (presence.priority(), show_rank(presence.show()), seq)
+            })
+            .map(|(resource, (_, presence))| (&resource[..], presence))
+    }
+
+    pub fn clear(&mut self) {
+        self.contacts.clear();
+    }
+
+    pub(crate) fn update(&mut self, presence: &Presence) {
+        let from = match presence.from() {
+            Some(from) => from,
+            None => return,
+        };
+        let bare = jid::bare(from);
+        let resource = jid::resource(from);
+
+        match presence.stanza_type() {
+            Some(PresenceType::Available) => {
+                self.seq += 1;
+                self.contacts
+                    .entry(bare.into())
+                    .or_default()
+                    .insert(resource.unwrap_or("").into(), (self.seq, presence.clone()));
+            }
+            // Errors are treated like unavailable presence (RFC 6121, section 4.6.3)
+            Some(PresenceType::Unavailable) | Some(PresenceType::Error) => {
+                let now_empty = match (self.contacts.get_mut(bare), resource) {
+                    (Some(resources), Some(resource)) => {
+                        resources.remove(resource);
+                        resources.is_empty()
+                    }
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                };
+                if now_empty {
+                    self.contacts.remove(bare);
+                }
+            }
+            _ => (),
+        }
+    }
+}
@@ -5,8 +5,8 @@
 // Please see the COPYING file for more information.
 
 use std::io;
-use std::io::BufRead;
-use std::str;
+use std::io::Read;
+use std::mem;
 
 // https://tools.ietf.org/html/rfc3629
 #[rustfmt::skip]
@@ -36,40 +36,139 @@ fn utf8_char_width(b: u8) -> usize {
 }
 
 pub trait ReadString {
+    /// Reads the next chunk of text, fails with `UnexpectedEof` once the peer
+    /// closed the connection
     fn read_str(&mut self) -> io::Result<String>;
 }
 
-impl<T: BufRead> ReadString for T {
+/// A buffered reader of UTF-8 text.
+///
+/// Characters split across reads are held back until they are complete.
+pub struct StrReader<R> {
+    inner: R,
+    /// The start of a character whose remaining bytes have not arrived yet
+    partial: Vec<u8>,
+}
+
+impl<R: Read> StrReader<R> {
+    pub fn new(inner: R) -> StrReader<R> {
+        StrReader {
+            inner,
+            partial: Vec::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+}
+
+/// The length of the longest prefix of `data` not ending in a partial character
+fn complete_len(data: &[u8]) -> usize {
+    let len = data.len();
+    let mut last = len.saturating_sub(3);
+    while last < len {
+        let width = utf8_char_width(data[last]);
+        if width == 0 {
+            last += 1;
+            continue;
+        }
+        if last + width <= len {
+            last += width;
+        } else {
+            break;
+        }
+    }
+    last
+}
+
+impl<R: Read> ReadString for StrReader<R> {
     fn read_str(&mut self) -> io::Result<String> {
-        let (result, last) = {
-            let available = self.fill_buf()?;
-            let len = available.len();
-            let mut last = if len < 3 { 0 } else { len - 3 };
-            while last < len {
-                let width = utf8_char_width(available[last]);
-                if width == 0 {
-                    last += 1;
-                    continue;
-                }
-                if last + width <= len {
-                    last += width;
-                } else {
-                    break;
+        let mut buf = [0; 8192];
+        loop {
+            // On errors, e.g. timeouts, a partial character is kept for the next call
+            let read = self.inner.read(&mut buf)?;
+            if read == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "connection closed by peer",
+                ));
+            }
+            let mut data = mem::take(&mut self.partial);
+            data.extend_from_slice(&buf[..read]);
+            self.partial = data.split_off(complete_len(&data));
+            if data.is_empty() {
+                continue;
+            }
+            return String::from_utf8(data).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                )
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Hands out one chunk per read, `None` simulates a read timeout
+    struct Chunks(VecDeque<Option<&'static [u8]>>);
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.pop_front() {
+                Some(Some(chunk)) => {
+                    buf[..chunk.len()].copy_from_slice(chunk);
+                    Ok(chunk.len())
                 }
+                Some(None) => Err(io::ErrorKind::WouldBlock.into()),
+                None => Ok(0),
             }
-            let res = str::from_utf8(&available[..last]);
-            (
-                res.map(|x| x.to_string()).map_err(|_| {
-                    io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "stream did not contain valid UTF-8",
-                    )
-                }),
-                last,
-            )
-        };
-        self.consume(last);
-
-        result
+        }
+    }
+
+    fn reader(chunks: &[Option<&'static [u8]>]) -> StrReader<Chunks> {
+        StrReader::new(Chunks(chunks.iter().cloned().collect()))
+    }
+
+    #[test]
+    fn split_character() {
+        let euro = "€".as_bytes();
+        let mut reader = reader(&[
+            Some(b"ab\xe2"),
+            Some(&euro[1..2]),
+            None,
+            Some(&euro[2..]),
+            Some(b"cd"),
+        ]);
+        assert_eq!(reader.read_str().unwrap(), "ab");
+        let err = reader.read_str().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(reader.read_str().unwrap(), "€");
+        assert_eq!(reader.read_str().unwrap(), "cd");
+        let err = reader.read_str().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn eof_within_character() {
+        let mut reader = reader(&[Some(b"\xe2\x82")]);
+        let err = reader.read_str().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let mut reader = reader(&[Some(b"a\xffb")]);
+        let err = reader.read_str().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
     }
 }
@@ -0,0 +1,187 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! Message Delivery Receipts (XEP-0184)
+
+use std::time::Instant;
+
+use crate::jid;
+use crate::ns;
+use crate::roster::Subscription;
+use crate::stanzas::{Message, MessageType, Payload, Stanza};
+use crate::{Event, XmppHandler, XmppStream};
+
+/// The `<request xmlns='urn:xmpp:receipts'/>` payload.
+///
+/// Add it to an outgoing message with an id to be notified by
+/// `Event::MessageDelivered` once the recipient received it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Request;
+
+impl Payload for Request {
+    const NAME: &'static str = "request";
+    const NS: &'static str = ns::RECEIPTS;
+
+    fn try_from(elem: &xml::Element) -> Result<Request, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a receipt request");
+        }
+        Ok(Request)
+    }
+
+    fn to_element(&self) -> xml::Element {
+        xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), vec![])
+    }
+}
+
+/// The `<received xmlns='urn:xmpp:receipts'/>` payload
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Received {
+    /// The id of the received message
+    pub id: Option<String>,
+}
+
+impl Payload for Received {
+    const NAME: &'static str = "received";
+    const NS: &'static str = ns::RECEIPTS;
+
+    fn try_from(elem: &xml::Element) -> Result<Received, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a receipt");
+        }
+        Ok(Received {
+            id: elem.get_attribute("id", None).map(|x| x.into()),
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let attrs = self.id.clone().map(|id| ("id".into(), None, id));
+        xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), attrs)
+    }
+}
+
+/// How many sent messages awaiting a receipt are tracked at most
+pub const MAX_PENDING_RECEIPTS: usize = 1000;
+
+/// A sent message awaiting its receipt
+pub(crate) struct PendingReceipt {
+    to: String,
+    sent: Instant,
+}
+
+/// Whom we confirm the receipt of messages to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReceiptPolicy {
+    Never,
+    Always,
+    /// Only contacts subscribed to our presence, and our own resources.
+    /// Others could otherwise find out whether we are online.
+    Subscribed,
+}
+
+impl XmppStream {
+    /// Sets when receipts are requested from us are sent, by default
+    /// `ReceiptPolicy::Subscribed`
+    pub fn set_receipt_policy(&mut self, policy: ReceiptPolicy) {
+        self.handler.receipt_policy = policy;
+        // Only advertise receipts if anyone can get them
+        if policy == ReceiptPolicy::Never {
+            self.remove_disco_feature(ns::RECEIPTS);
+        } else {
+            self.add_disco_feature(ns::RECEIPTS);
+        }
+    }
+
+    /// The ids of sent messages whose receipt was requested, but not yet confirmed.
+    ///
+    /// Only the `MAX_PENDING_RECEIPTS` most recent messages are tracked.
+    pub fn pending_receipts(&self) -> impl Iterator<Item = &str> {
+        self.handler.pending_receipts.keys().map(|id| &id[..])
+    }
+}
+
+impl XmppHandler {
+    /// Remembers outgoing messages requesting a receipt
+    pub(crate) fn track_receipt(&mut self, message: &Message) {
+        if message.get_payload::<Request>().is_none() {
+            return;
+        }
+        if let Some(id) = message.id() {
+            if self.pending_receipts.len() >= MAX_PENDING_RECEIPTS {
+                let oldest = self
+                    .pending_receipts
+                    .iter()
+                    .min_by_key(|(_, pending)| pending.sent)
+                    .map(|(id, _)| id.clone());
+                if let Some(oldest) = oldest {
+                    self.pending_receipts.remove(&oldest);
+                }
+            }
+            let pending = PendingReceipt {
+                to: message.to().unwrap_or("").into(),
+                sent: Instant::now(),
+            };
+            self.pending_receipts.insert(id.into(), pending);
+        }
+    }
+
+    /// Answers receipt requests and consumes receipts for our messages,
+    /// returns all other messages
+    pub(crate) fn handle_receipts(&mut self, message: Message) -> Option<Message> {
+        let from = message.from().unwrap_or("").to_string();
+        let id = message.id().map(|x| x.to_string());
+        match message.stanza_type() {
+            // The message will never be delivered
+            Some(MessageType::Error) => {
+                if let Some(id) = id {
+                    self.pending_receipts.remove(&id);
+                }
+                return Some(message);
+            }
+            Some(MessageType::Groupchat) => return Some(message),
+            _ => (),
+        }
+
+        if let Some(received) = message.get_payload::<Received>() {
+            // Receipts must come from the recipient, or any of its resources
+            let pending = received.id.filter(|id| {
+                self.pending_receipts
+                    .get(id)
+                    .is_some_and(|pending| jid::bare(&pending.to) == jid::bare(&from))
+            });
+            if let Some(id) = pending {
+                self.pending_receipts.remove(&id);
+                self.events.push_back(Event::MessageDelivered(id));
+                return None;
+            }
+        }
+
+        if let Some(id) = id {
+            if message.get_payload::<Request>().is_some() && self.receipt_allowed(&from) {
+                let mut receipt = Message::new(MessageType::Normal, self.next_id());
+                receipt.set_to(Some(from));
+                receipt.add_payload(Received { id: Some(id) });
+                let _ = self.send(receipt);
+            }
+        }
+        Some(message)
+    }
+
+    fn receipt_allowed(&self, from: &str) -> bool {
+        match self.receipt_policy {
+            ReceiptPolicy::Never => false,
+            ReceiptPolicy::Always => true,
+            ReceiptPolicy::Subscribed => {
+                let bare = jid::bare(from);
+                let own = self.jid.as_deref().map(jid::bare) == Some(bare);
+                let subscribed = self.roster.get(bare).is_some_and(|item| {
+                    matches!(item.subscription, Subscription::From | Subscription::Both)
+                });
+                own || subscribed
+            }
+        }
+    }
+}
@@ -0,0 +1,402 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! Roster management (RFC 6121, section 2)
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::jid;
+use crate::ns;
+use crate::stanzas::{DefinedCondition, ErrorType, Iq, IqType, Payload, Stanza};
+use crate::{iq_error, malformed, Event, PendingIqKind, XmppHandler, XmppStream};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Subscription {
+    None,
+    To,
+    From,
+    Both,
+    /// Only used to remove items
+    Remove,
+}
+
+impl Subscription {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            Subscription::None => "none",
+            Subscription::To => "to",
+            Subscription::From => "from",
+            Subscription::Both => "both",
+            Subscription::Remove => "remove",
+        }
+    }
+
+    fn from_str(subscription: &str) -> Option<Subscription> {
+        match subscription {
+            "none" => Some(Subscription::None),
+            "to" => Some(Subscription::To),
+            "from" => Some(Subscription::From),
+            "both" => Some(Subscription::Both),
+            "remove" => Some(Subscription::Remove),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RosterItem {
+    pub jid: String,
+    pub name: Option<String>,
+    pub subscription: Subscription,
+    /// A subscription request to the contact is pending
+    pub ask: bool,
+    pub groups: Vec<String>,
+}
+
+impl RosterItem {
+    pub fn new(jid: String) -> RosterItem {
+        RosterItem {
+            jid,
+            name: None,
+            subscription: Subscription::None,
+            ask: false,
+            groups: vec![],
+        }
+    }
+
+    fn from_element(item: &xml::Element) -> Result<RosterItem, &'static str> {
+        let jid = item
+            .get_attribute("jid", None)
+            .ok_or("Roster item without JID")?;
+        let subscription = match item.get_attribute("subscription", None) {
+            Some(sub) => Subscription::from_str(sub).ok_or("Invalid roster subscription")?,
+            None => Subscription::None,
+        };
+        Ok(RosterItem {
+            jid: jid.into(),
+            name: item.get_attribute("name", None).map(|x| x.into()),
+            subscription,
+            ask: item.get_attribute("ask", None) == Some("subscribe"),
+            groups: item
+                .get_children("group", Some(ns::ROSTER))
+                .map(|group| group.content_str())
+                .collect(),
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut attrs = vec![("jid".into(), None, self.jid.clone())];
+        if let Some(ref name) = self.name {
+            attrs.push(("name".into(), None, name.clone()));
+        }
+        if self.subscription != Subscription::None {
+            attrs.push((
+                "subscription".into(),
+                None,
+                self.subscription.as_str().into(),
+            ));
+        }
+        if self.ask {
+            attrs.push(("ask".into(), None, "subscribe".into()));
+        }
+        let mut item = xml::Element::new("item".into(), Some(ns::ROSTER.into()), attrs);
+        for group in &self.groups {
+            item.tag(xml::Element::new(
+                "group".into(),
+                Some(ns::ROSTER.into()),
+                vec![],
+            ))
+            .text(group.clone());
+        }
+        item
+    }
+}
+
+/// The `<query xmlns='jabber:iq:roster'/>` payload
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RosterQuery {
+    pub ver: Option<String>,
+    pub items: Vec<RosterItem>,
+}
+
+impl Payload for RosterQuery {
+    const NAME: &'static str = "query";
+    const NS: &'static str = ns::ROSTER;
+
+    fn try_from(elem: &xml::Element) -> Result<RosterQuery, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a roster query");
+        }
+        let items = elem
+            .get_children("item", Some(Self::NS))
+            .map(RosterItem::from_element)
+            .collect::<Result<_, _>>()?;
+        Ok(RosterQuery {
+            ver: elem.get_attribute("ver", None).map(|x| x.into()),
+            items,
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let attrs = self.ver.clone().map(|ver| ("ver".into(), None, ver));
+        let mut query = xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), attrs);
+        for item in &self.items {
+            query.tag(item.to_element());
+        }
+        query
+    }
+}
+
+/// The user's contact list, as last received from the server
+#[derive(Clone, Debug, Default)]
+pub struct Roster {
+    items: BTreeMap<String, RosterItem>,
+    ver: Option<String>,
+}
+
+impl Roster {
+    pub fn new(ver: Option<String>, items: Vec<RosterItem>) -> Roster {
+        let mut roster = Roster::default();
+        roster.replace(ver, items);
+        roster
+    }
+
+    /// The roster version (RFC 6121, section 2.6), if the server supports versioning
+    pub fn version(&self) -> Option<&str> {
+        self.ver.as_deref()
+    }
+
+    pub fn get(&self, jid: &str) -> Option<&RosterItem> {
+        self.items.get(jid)
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &RosterItem> {
+        self.items.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn replace(&mut self, ver: Option<String>, items: Vec<RosterItem>) {
+        self.ver = ver;
+        self.items = items
+            .into_iter()
+            .map(|item| (item.jid.clone(), item))
+            .collect();
+    }
+}
+
+/// Persistence for the roster and its version between sessions
+pub trait RosterStore {
+    /// Loads the cached roster, if there is one
+    fn load(&mut self) -> io::Result<Option<Roster>>;
+    fn save(&mut self, roster: &Roster) -> io::Result<()>;
+}
+
+/// A `RosterStore` keeping the roster in memory, e.g. across reconnects
+#[derive(Default)]
+pub struct MemoryRosterStore {
+    roster: Option<Roster>,
+}
+
+impl MemoryRosterStore {
+    pub fn new() -> MemoryRosterStore {
+        MemoryRosterStore::default()
+    }
+}
+
+impl RosterStore for MemoryRosterStore {
+    fn load(&mut self) -> io::Result<Option<Roster>> {
+        Ok(self.roster.clone())
+    }
+
+    fn save(&mut self, roster: &Roster) -> io::Result<()> {
+        self.roster = Some(roster.clone());
+        Ok(())
+    }
+}
+
+/// A `RosterStore` keeping the roster in a file, as a roster `<query/>` element
+pub struct FileRosterStore {
+    path: PathBuf,
+}
+
+impl FileRosterStore {
+    pub fn new<P: Into<PathBuf>>(path: P) -> FileRosterStore {
+        FileRosterStore { path: path.into() }
+    }
+}
+
+impl RosterStore for FileRosterStore {
+    fn load(&mut self) -> io::Result<Option<Roster>> {
+        let data = match fs::read_to_string(&self.path) {
+            Ok(data) => data,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+        let elem: xml::Element = data
+            .parse()
+            .map_err(|_| invalid("Roster file is not valid XML"))?;
+        let query = <RosterQuery as Payload>::try_from(&elem).map_err(invalid)?;
+        Ok(Some(Roster::new(query.ver, query.items)))
+    }
+
+    fn save(&mut self, roster: &Roster) -> io::Result<()> {
+        let query = RosterQuery {
+            ver: roster.ver.clone(),
+            items: roster.items().cloned().collect(),
+        };
+        // Write to a temporary file first, so a crash can't leave a truncated roster behind
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, query.to_element().to_string())?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+impl XmppStream {
+    pub fn roster(&self) -> &Roster {
+        &self.handler.roster
+    }
+
+    /// Uses `store` to cache the roster between sessions.
+    ///
+    /// The cached roster is loaded immediately, and its version is offered to
+    /// servers supporting roster versioning, so only changes have to be sent.
+    pub fn set_roster_store(&mut self, mut store: Box<dyn RosterStore>) -> io::Result<()> {
+        if let Some(roster) = store.load()? {
+            self.handler.roster = roster;
+        }
+        self.handler.roster_store = Some(store);
+        Ok(())
+    }
+
+    /// Requests the roster from the server.
+    ///
+    /// Returns the id of the request. Once the roster arrived
+    /// `Event::RosterReceived` is emitted, on failure `Event::RosterResult`.
+    pub fn fetch_roster(&mut self) -> io::Result<String> {
+        let versioning = self.handler.features.as_ref().is_some_and(|f| f.roster_ver);
+        let ver = if versioning {
+            // An empty version requests the full roster, but signals support
+            Some(self.handler.roster.ver.clone().unwrap_or_default())
+        } else {
+            None
+        };
+
+        let mut iq = Iq::new(IqType::Get, String::new());
+        iq.add_payload(RosterQuery { ver, items: vec![] });
+        self.handler.send_iq(iq, PendingIqKind::RosterGet)
+    }
+
+    /// Adds `item` to the roster, or updates it if it exists.
+    ///
+    /// The `subscription` and `ask` fields are managed by the server and not sent.
+    /// Returns the id of the request, the outcome is reported by `Event::RosterResult`.
+    pub fn set_roster_item(&mut self, mut item: RosterItem) -> io::Result<String> {
+        if item.subscription != Subscription::Remove {
+            item.subscription = Subscription::None;
+        }
+        item.ask = false;
+        let mut iq = Iq::new(IqType::Set, String::new());
+        iq.add_payload(RosterQuery {
+            ver: None,
+            items: vec![item],
+        });
+        self.handler.send_iq(iq, PendingIqKind::RosterSet)
+    }
+
+    /// Removes the item with `jid` from the roster.
+    ///
+    /// Returns the id of the request, the outcome is reported by `Event::RosterResult`.
+    pub fn remove_roster_item(&mut self, jid: &str) -> io::Result<String> {
+        let mut item = RosterItem::new(jid.into());
+        item.subscription = Subscription::Remove;
+        self.set_roster_item(item)
+    }
+}
+
+impl XmppHandler {
+    pub(crate) fn handle_roster_result(&mut self, iq: Iq) {
+        if let Some(IqType::Error) = iq.stanza_type() {
+            let id = iq.id().unwrap().into();
+            self.events
+                .push_back(Event::RosterResult(id, Err(iq_error(&iq))));
+            return;
+        }
+
+        // An empty result means our cached roster is up to date
+        if let Some(query) = iq.get_child(RosterQuery::NAME, Some(RosterQuery::NS)) {
+            match <RosterQuery as Payload>::try_from(query) {
+                Ok(query) => {
+                    self.roster.replace(query.ver, query.items);
+                    self.save_roster();
+                }
+                Err(_) => {
+                    let id = iq.id().unwrap().into();
+                    self.events
+                        .push_back(Event::RosterResult(id, Err(malformed())));
+                    return;
+                }
+            }
+        }
+        self.events.push_back(Event::RosterReceived);
+    }
+
+    pub(crate) fn handle_roster_push(&mut self, iq: Iq) {
+        // Pushes must come from our own account (RFC 6121, section 2.1.6)
+        let own_bare = self.jid.as_deref().map(jid::bare);
+        if iq.from().is_some() && iq.from() != own_bare {
+            let reply = iq.error_reply(
+                ErrorType::Cancel,
+                DefinedCondition::ServiceUnavailable,
+                None,
+            );
+            let _ = self.send(reply);
+            return;
+        }
+
+        let (ver, item) = match iq.get_payload::<RosterQuery>() {
+            Some(mut query) if query.items.len() == 1 => (query.ver, query.items.pop().unwrap()),
+            _ => {
+                let reply = iq.error_reply(ErrorType::Modify, DefinedCondition::BadRequest, None);
+                let _ = self.send(reply);
+                return;
+            }
+        };
+
+        if item.subscription == Subscription::Remove {
+            self.roster.items.remove(&item.jid);
+            self.events.push_back(Event::RosterItemRemoved(item.jid));
+        } else {
+            self.roster.items.insert(item.jid.clone(), item.clone());
+            self.events.push_back(Event::RosterItemChanged(item));
+        }
+        if ver.is_some() {
+            self.roster.ver = ver;
+        }
+        self.save_roster();
+
+        let _ = self.send(iq.result_reply());
+    }
+
+    fn save_roster(&mut self) {
+        if let Some(ref mut store) = self.roster_store {
+            if let Err(e) = store.save(&self.roster) {
+                self.events.push_back(Event::RosterStoreError(e));
+            }
+        }
+    }
+}
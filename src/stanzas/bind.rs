@@ -0,0 +1,59 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+use crate::ns;
+use xml;
+
+use super::Payload;
+
+/// Resource binding (RFC 6120, section 7)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bind {
+    /// The resource requested by the client
+    pub resource: Option<String>,
+    /// The full JID assigned by the server
+    pub jid: Option<String>,
+}
+
+impl Payload for Bind {
+    const NAME: &'static str = "bind";
+    const NS: &'static str = ns::FEATURE_BIND;
+
+    fn try_from(elem: &xml::Element) -> Result<Bind, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a bind element");
+        }
+        let text = |name| {
+            elem.get_child(name, Some(Self::NS))
+                .map(|e| e.content_str())
+        };
+        Ok(Bind {
+            resource: text("resource"),
+            jid: text("jid"),
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut bind = xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), vec![]);
+        if let Some(ref resource) = self.resource {
+            bind.tag(xml::Element::new(
+                "resource".into(),
+                Some(Self::NS.into()),
+                vec![],
+            ))
+            .text(resource.clone());
+        }
+        if let Some(ref jid) = self.jid {
+            bind.tag(xml::Element::new(
+                "jid".into(),
+                Some(Self::NS.into()),
+                vec![],
+            ))
+            .text(jid.clone());
+        }
+        bind
+    }
+}
@@ -0,0 +1,70 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+use std::time::SystemTime;
+
+use crate::date_time;
+use crate::ns;
+use xml;
+
+use super::Payload;
+
+/// Delayed Delivery (XEP-0203)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Delay {
+    /// When the stanza was originally sent
+    pub stamp: SystemTime,
+    /// The entity that delayed the stanza
+    pub from: Option<String>,
+    pub reason: Option<String>,
+}
+
+impl Delay {
+    pub fn new(stamp: SystemTime) -> Delay {
+        Delay {
+            stamp,
+            from: None,
+            reason: None,
+        }
+    }
+}
+
+impl Payload for Delay {
+    const NAME: &'static str = "delay";
+    const NS: &'static str = ns::DELAY;
+
+    fn try_from(elem: &xml::Element) -> Result<Delay, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a delay element");
+        }
+        let stamp = elem
+            .get_attribute("stamp", None)
+            .and_then(date_time::parse)
+            .ok_or("Delay without valid stamp")?;
+        let reason = elem.content_str();
+        Ok(Delay {
+            stamp,
+            from: elem.get_attribute("from", None).map(|x| x.into()),
+            reason: if reason.is_empty() {
+                None
+            } else {
+                Some(reason)
+            },
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut attrs = vec![("stamp".into(), None, date_time::format(self.stamp))];
+        if let Some(ref from) = self.from {
+            attrs.push(("from".into(), None, from.clone()));
+        }
+        let mut delay = xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), attrs);
+        if let Some(ref reason) = self.reason {
+            delay.text(reason.clone());
+        }
+        delay
+    }
+}
@@ -0,0 +1,90 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+use std::fmt;
+
+use crate::ns;
+use xml;
+
+use super::{DefinedCondition, ErrorType};
+
+/// An error reported in a stanza of type `error` (RFC 6120, section 8.3)
+#[derive(Clone, Debug, PartialEq)]
+pub struct StanzaError {
+    pub ty: ErrorType,
+    pub condition: DefinedCondition,
+    /// Human readable description of the error
+    pub text: Option<String>,
+    /// The `xml:lang` of `text`
+    pub text_lang: Option<String>,
+    /// The entity that generated the error
+    pub by: Option<String>,
+    /// An application-specific condition element
+    pub app_condition: Option<xml::Element>,
+}
+
+impl StanzaError {
+    pub fn new(ty: ErrorType, condition: DefinedCondition) -> StanzaError {
+        StanzaError {
+            ty,
+            condition,
+            text: None,
+            text_lang: None,
+            by: None,
+            app_condition: None,
+        }
+    }
+
+    pub fn from_element(error: &xml::Element) -> Result<StanzaError, &'static str> {
+        let ty = error
+            .get_attribute("type", None)
+            .and_then(ErrorType::from_attr_string)
+            .ok_or("Stanza error without valid type")?;
+
+        let mut condition = None;
+        let mut text = None;
+        let mut text_lang = None;
+        let mut app_condition = None;
+        for child in &error.children {
+            let child = match *child {
+                xml::Xml::ElementNode(ref child) => child,
+                _ => continue,
+            };
+            if child.ns.as_deref() != Some(ns::STANZA_ERRORS) {
+                if app_condition.is_none() {
+                    app_condition = Some(child.clone());
+                }
+            } else if child.name == "text" {
+                text = Some(child.content_str());
+                text_lang = child.get_attribute("lang", Some(ns::XML)).map(|x| x.into());
+            } else if condition.is_none() {
+                condition = DefinedCondition::from_element(child);
+            }
+        }
+
+        // Unknown conditions are to be treated as undefined-condition
+        let condition = condition.unwrap_or(DefinedCondition::UndefinedCondition);
+
+        Ok(StanzaError {
+            ty,
+            condition,
+            text,
+            text_lang,
+            by: error.get_attribute("by", None).map(|x| x.into()),
+            app_condition,
+        })
+    }
+}
+
+impl fmt::Display for StanzaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.ty.attr_string(), self.condition.name())?;
+        if let Some(ref text) = self.text {
+            write!(f, " ({})", text)?;
+        }
+        Ok(())
+    }
+}
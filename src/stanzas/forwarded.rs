@@ -0,0 +1,65 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+use crate::ns;
+use xml;
+
+use super::{AStanza, Delay, Message, Payload, Stanza};
+
+/// Stanza Forwarding (XEP-0297)
+#[derive(Clone)]
+pub struct Forwarded {
+    /// When the stanza was originally sent or received
+    pub delay: Option<Delay>,
+    pub stanza: AStanza,
+}
+
+impl Forwarded {
+    /// The forwarded stanza, if it is a message
+    pub fn into_message(self) -> Option<Message> {
+        match self.stanza {
+            AStanza::MessageStanza(message) => Some(message),
+            _ => None,
+        }
+    }
+}
+
+impl Payload for Forwarded {
+    const NAME: &'static str = "forwarded";
+    const NS: &'static str = ns::FORWARD;
+
+    fn try_from(elem: &xml::Element) -> Result<Forwarded, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a forwarded element");
+        }
+        let delay = match elem.get_child(Delay::NAME, Some(Delay::NS)) {
+            Some(delay) => Some(<Delay as Payload>::try_from(delay)?),
+            None => None,
+        };
+        let stanza = elem
+            .children
+            .iter()
+            .find_map(|child| match *child {
+                xml::Xml::ElementNode(ref child) => AStanza::from_element(child.clone()).ok(),
+                _ => None,
+            })
+            .ok_or("Forwarded element without stanza")?;
+        Ok(Forwarded { delay, stanza })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut forwarded = xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), vec![]);
+        if let Some(ref delay) = self.delay {
+            forwarded.tag(delay.to_element());
+        }
+        forwarded.tag(match self.stanza {
+            AStanza::IqStanza(ref iq) => iq.as_element().clone(),
+            AStanza::MessageStanza(ref message) => message.as_element().clone(),
+            AStanza::PresenceStanza(ref presence) => presence.as_element().clone(),
+        });
+        forwarded
+    }
+}
@@ -7,7 +7,7 @@
 use crate::ns;
 use xml;
 
-use super::{Stanza, StanzaType};
+use super::{Bind, Stanza, StanzaType};
 
 #[derive(Copy, Clone, Debug)]
 pub enum IqType {
@@ -59,11 +59,22 @@ impl Iq {
         }
     }
 
+    /// The first child element, which determines the semantics of a request
+    pub fn payload(&self) -> Option<&xml::Element> {
+        self.elem.children.iter().find_map(|child| match *child {
+            xml::Xml::ElementNode(ref elem) => Some(elem),
+            _ => None,
+        })
+    }
+
+    /// An empty `result` response to this request
+    pub fn result_reply(&self) -> Iq {
+        let mut reply = Iq::new(IqType::Result, self.id().unwrap_or("").into());
+        reply.set_to(self.from().map(|x| x.into()));
+        reply
+    }
+
     pub fn get_xmpp_bind_jid(&self) -> Option<String> {
-        let ns = Some(ns::FEATURE_BIND);
-        self.elem
-            .get_child("bind", ns)
-            .and_then(|bind| bind.get_child("jid", ns))
-            .map(|jid| jid.content_str())
+        self.get_payload::<Bind>().and_then(|bind| bind.jid)
     }
 }
@@ -0,0 +1,78 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+use std::collections::BTreeMap;
+
+use crate::ns;
+use xml;
+
+/// Texts keyed by their `xml:lang`. Texts of unknown language use an empty key.
+pub type Texts = BTreeMap<String, String>;
+
+fn primary_tag(lang: &str) -> &str {
+    lang.split('-').next().unwrap_or(lang)
+}
+
+/// Picks the text best matching `lang`.
+///
+/// Exact matches are preferred over matches of the primary language subtag.
+/// Failing that the text in `default_lang`, the text of unknown language,
+/// or any text is returned, in that order.
+pub fn best_match<'a>(texts: &'a Texts, lang: &str, default_lang: Option<&str>) -> Option<&'a str> {
+    let exact = texts.iter().find(|&(l, _)| l.eq_ignore_ascii_case(lang));
+    let primary = || {
+        texts
+            .iter()
+            .find(|&(l, _)| !l.is_empty() && primary_tag(l).eq_ignore_ascii_case(primary_tag(lang)))
+    };
+    let default = || default_lang.and_then(|d| texts.iter().find(|&(l, _)| l == d));
+    exact
+        .or_else(primary)
+        .or_else(default)
+        .map(|(_, text)| text)
+        .or_else(|| texts.get(""))
+        .or_else(|| texts.values().next())
+        .map(|x| &x[..])
+}
+
+/// Collects the text of all `name` children of `elem`, keyed by their
+/// effective `xml:lang`.
+pub(crate) fn get_texts(elem: &xml::Element, name: &str, ns: &str) -> Texts {
+    let parent_lang = elem.get_attribute("lang", Some(ns::XML)).unwrap_or("");
+    elem.get_children(name, Some(ns))
+        .map(|child| {
+            let lang = child
+                .get_attribute("lang", Some(ns::XML))
+                .unwrap_or(parent_lang);
+            (lang.to_string(), child.content_str())
+        })
+        .collect()
+}
+
+/// Replaces the `name` child of `elem` that has the `xml:lang` `lang`.
+/// If `text` is `None` the child is only removed.
+pub(crate) fn set_text(
+    elem: &mut xml::Element,
+    name: &str,
+    ns: &str,
+    lang: Option<&str>,
+    text: Option<String>,
+) {
+    elem.children.retain(|child| match *child {
+        xml::Xml::ElementNode(ref child) => {
+            child.name != name
+                || child.ns.as_deref() != Some(ns)
+                || child.get_attribute("lang", Some(ns::XML)) != lang
+        }
+        _ => true,
+    });
+
+    if let Some(text) = text {
+        let attrs = lang.map(|lang| ("lang".into(), Some(ns::XML.into()), lang.into()));
+        elem.tag(xml::Element::new(name.into(), Some(ns.into()), attrs))
+            .text(text);
+    }
+}
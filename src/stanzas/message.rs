@@ -7,6 +7,7 @@
 use crate::ns;
 use xml;
 
+use super::lang::{best_match, get_texts, set_text, Texts};
 use super::{Stanza, StanzaType};
 
 #[derive(Copy, Clone)]
@@ -30,6 +31,13 @@ impl StanzaType for MessageType {
     }
 }
 
+/// A conversation thread, optionally spawned from a `parent` thread
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Thread {
+    pub id: String,
+    pub parent: Option<String>,
+}
+
 #[derive(Clone)]
 pub struct Message {
     elem: xml::Element,
@@ -67,4 +75,61 @@ impl Message {
             ),
         }
     }
+
+    /// All `<body/>` texts, keyed by language
+    pub fn bodies(&self) -> Texts {
+        get_texts(&self.elem, "body", ns::JABBER_CLIENT)
+    }
+
+    /// The `<body/>` best matching `lang`, or the message's default language
+    pub fn body(&self, lang: Option<&str>) -> Option<String> {
+        let bodies = self.bodies();
+        let default = self.lang();
+        best_match(&bodies, lang.or(default).unwrap_or(""), default).map(|x| x.into())
+    }
+
+    /// Sets the `<body/>` for `lang`, or removes it if `body` is `None`
+    pub fn set_body(&mut self, lang: Option<&str>, body: Option<String>) {
+        set_text(&mut self.elem, "body", ns::JABBER_CLIENT, lang, body);
+    }
+
+    /// All `<subject/>` texts, keyed by language
+    pub fn subjects(&self) -> Texts {
+        get_texts(&self.elem, "subject", ns::JABBER_CLIENT)
+    }
+
+    /// The `<subject/>` best matching `lang`, or the message's default language
+    pub fn subject(&self, lang: Option<&str>) -> Option<String> {
+        let subjects = self.subjects();
+        let default = self.lang();
+        best_match(&subjects, lang.or(default).unwrap_or(""), default).map(|x| x.into())
+    }
+
+    /// Sets the `<subject/>` for `lang`, or removes it if `subject` is `None`
+    pub fn set_subject(&mut self, lang: Option<&str>, subject: Option<String>) {
+        set_text(&mut self.elem, "subject", ns::JABBER_CLIENT, lang, subject);
+    }
+
+    pub fn thread(&self) -> Option<Thread> {
+        self.elem
+            .get_child("thread", Some(ns::JABBER_CLIENT))
+            .map(|thread| Thread {
+                id: thread.content_str(),
+                parent: thread.get_attribute("parent", None).map(|x| x.into()),
+            })
+    }
+
+    pub fn set_thread(&mut self, thread: Option<Thread>) {
+        set_text(&mut self.elem, "thread", ns::JABBER_CLIENT, None, None);
+        if let Some(thread) = thread {
+            let attrs = thread.parent.map(|parent| ("parent".into(), None, parent));
+            self.elem
+                .tag(xml::Element::new(
+                    "thread".into(),
+                    Some(ns::JABBER_CLIENT.into()),
+                    attrs,
+                ))
+                .text(thread.id);
+        }
+    }
 }
@@ -7,13 +7,25 @@
 use crate::ns;
 use xml;
 
+pub use self::bind::Bind;
+pub use self::delay::Delay;
+pub use self::error::StanzaError;
+pub use self::forwarded::Forwarded;
 pub use self::iq::Iq;
 pub use self::iq::IqType;
+pub use self::lang::best_match;
+pub use self::lang::Texts;
 pub use self::message::Message;
 pub use self::message::MessageType;
+pub use self::message::Thread;
+pub use self::payload::Payload;
 pub use self::presence::Presence;
 pub use self::presence::PresenceType;
+pub use self::presence::Show;
+pub use self::rsm::Pager;
+pub use self::rsm::ResultSet;
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ErrorType {
     Auth,
     Cancel,
@@ -32,8 +44,20 @@ impl ErrorType {
             ErrorType::Wait => "wait",
         }
     }
+
+    fn from_attr_string(ty: &str) -> Option<ErrorType> {
+        match ty {
+            "auth" => Some(ErrorType::Auth),
+            "cancel" => Some(ErrorType::Cancel),
+            "continue" => Some(ErrorType::Continue),
+            "modify" => Some(ErrorType::Modify),
+            "wait" => Some(ErrorType::Wait),
+            _ => None,
+        }
+    }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum DefinedCondition {
     BadRequest,
     Conflict,
@@ -60,18 +84,13 @@ pub enum DefinedCondition {
 }
 
 impl DefinedCondition {
-    fn element(self) -> xml::Element {
-        let name = match self {
+    fn name(&self) -> &'static str {
+        match *self {
             DefinedCondition::BadRequest => "bad-request",
             DefinedCondition::Conflict => "conflict",
             DefinedCondition::FeatureNotImplemented => "feature-not-implemented",
             DefinedCondition::Forbidden => "forbidden",
-            DefinedCondition::Gone(g) => {
-                let mut gone =
-                    xml::Element::new("gone".into(), Some(ns::STANZA_ERRORS.into()), vec![]);
-                gone.text(g);
-                return gone;
-            }
+            DefinedCondition::Gone(_) => "gone",
             DefinedCondition::InternalServerError => "internal-server-error",
             DefinedCondition::ItemNotFound => "item-not-found",
             DefinedCondition::JidMalformed => "jid-malformed",
@@ -80,12 +99,7 @@ impl DefinedCondition {
             DefinedCondition::NotAuthorized => "not-authorized",
             DefinedCondition::PolicyViolation => "policy-violation",
             DefinedCondition::RecipientUnavailable => "recipient-unavailable",
-            DefinedCondition::Redirect(r) => {
-                let mut redirect =
-                    xml::Element::new("redirect".into(), Some(ns::STANZA_ERRORS.into()), vec![]);
-                redirect.text(r);
-                return redirect;
-            }
+            DefinedCondition::Redirect(_) => "redirect",
             DefinedCondition::RegistrationRequired => "registration-required",
             DefinedCondition::RemoteServerNotFound => "remote-server-not-found",
             DefinedCondition::RemoteServerTimeout => "remote-server-timeout",
@@ -94,8 +108,50 @@ impl DefinedCondition {
             DefinedCondition::SubscriptionRequired => "subscription-required",
             DefinedCondition::UndefinedCondition => "undefined-condition",
             DefinedCondition::UnexpectedRequest => "unexpected-request",
-        };
-        xml::Element::new(name.into(), Some(ns::STANZA_ERRORS.into()), vec![])
+        }
+    }
+
+    fn element(self) -> xml::Element {
+        let mut elem =
+            xml::Element::new(self.name().into(), Some(ns::STANZA_ERRORS.into()), vec![]);
+        match self {
+            DefinedCondition::Gone(uri) | DefinedCondition::Redirect(uri) => {
+                elem.text(uri);
+            }
+            _ => (),
+        }
+        elem
+    }
+
+    fn from_element(elem: &xml::Element) -> Option<DefinedCondition> {
+        if elem.ns.as_deref() != Some(ns::STANZA_ERRORS) {
+            return None;
+        }
+        Some(match &elem.name[..] {
+            "bad-request" => DefinedCondition::BadRequest,
+            "conflict" => DefinedCondition::Conflict,
+            "feature-not-implemented" => DefinedCondition::FeatureNotImplemented,
+            "forbidden" => DefinedCondition::Forbidden,
+            "gone" => DefinedCondition::Gone(elem.content_str()),
+            "internal-server-error" => DefinedCondition::InternalServerError,
+            "item-not-found" => DefinedCondition::ItemNotFound,
+            "jid-malformed" => DefinedCondition::JidMalformed,
+            "not-acceptable" => DefinedCondition::NotAcceptable,
+            "not-allowed" => DefinedCondition::NotAllowed,
+            "not-authorized" => DefinedCondition::NotAuthorized,
+            "policy-violation" => DefinedCondition::PolicyViolation,
+            "recipient-unavailable" => DefinedCondition::RecipientUnavailable,
+            "redirect" => DefinedCondition::Redirect(elem.content_str()),
+            "registration-required" => DefinedCondition::RegistrationRequired,
+            "remote-server-not-found" => DefinedCondition::RemoteServerNotFound,
+            "remote-server-timeout" => DefinedCondition::RemoteServerTimeout,
+            "resource-constraint" => DefinedCondition::ResourceConstraint,
+            "service-unavailable" => DefinedCondition::ServiceUnavailable,
+            "subscription-required" => DefinedCondition::SubscriptionRequired,
+            "undefined-condition" => DefinedCondition::UndefinedCondition,
+            "unexpected-request" => DefinedCondition::UnexpectedRequest,
+            _ => return None,
+        })
     }
 }
 
@@ -106,8 +162,11 @@ pub trait StanzaType {
 pub trait Stanza: Sized {
     type Ty: StanzaType;
 
+    // Hands back the unmodified element on failure
+    #[allow(clippy::result_large_err)]
     fn from_element(e: xml::Element) -> Result<Self, xml::Element>;
     fn as_element(&self) -> &xml::Element;
+    fn as_element_mut(&mut self) -> &mut xml::Element;
     fn into_inner(self) -> xml::Element;
 
     fn to(&self) -> Option<&str>;
@@ -115,12 +174,39 @@ pub trait Stanza: Sized {
     fn id(&self) -> Option<&str>;
     fn stanza_type(&self) -> Option<Self::Ty>;
 
+    /// The `xml:lang` of the stanza
+    fn lang(&self) -> Option<&str> {
+        self.as_element().get_attribute("lang", Some(ns::XML))
+    }
+
     fn set_to(&mut self, to: Option<String>);
     fn set_from(&mut self, from: Option<String>);
     fn set_id(&mut self, id: Option<String>);
     fn set_stanza_type(&mut self, ty: Self::Ty);
 
     fn error_reply(&self, ty: ErrorType, cond: DefinedCondition, text: Option<String>) -> Self;
+
+    /// Parses the first child matching the payload type `T`
+    fn get_payload<T: Payload>(&self) -> Option<T> {
+        self.as_element()
+            .get_child(T::NAME, Some(T::NS))
+            .and_then(|elem| T::try_from(elem).ok())
+    }
+
+    /// Appends `payload` as a child of the stanza
+    fn add_payload<T: Payload>(&mut self, payload: T) {
+        self.as_element_mut().tag(payload.to_element());
+    }
+
+    /// Parses the `<error/>` child of a stanza of type `error`
+    fn error(&self) -> Option<StanzaError> {
+        let elem = self.as_element();
+        if elem.get_attribute("type", None) != Some("error") {
+            return None;
+        }
+        elem.get_child("error", elem.ns.as_deref())
+            .and_then(|error| StanzaError::from_element(error).ok())
+    }
 }
 
 macro_rules! impl_Stanza(
@@ -145,6 +231,10 @@ macro_rules! impl_Stanza(
                 &self.elem
             }
 
+            fn as_element_mut(&mut self) -> &mut xml::Element {
+                &mut self.elem
+            }
+
             fn into_inner(self) -> xml::Element {
                 self.elem
             }
@@ -250,10 +340,18 @@ macro_rules! impl_Stanza(
 );
 
 // Has to be after impl_Stanza!
+mod bind;
+mod delay;
+mod error;
+mod forwarded;
 mod iq;
+mod lang;
 mod message;
+mod payload;
 mod presence;
+mod rsm;
 
+#[derive(Clone)]
 pub enum AStanza {
     IqStanza(Iq),
     MessageStanza(Message),
@@ -261,6 +359,7 @@ pub enum AStanza {
 }
 
 impl AStanza {
+    #[allow(clippy::result_large_err)]
     pub fn from_element(e: xml::Element) -> Result<AStanza, xml::Element> {
         match e.ns {
             Some(ref ns) if *ns == ns::JABBER_CLIENT || *ns == ns::JABBER_SERVER => (),
@@ -0,0 +1,18 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+use xml;
+
+/// A typed extension element carried inside a stanza
+pub trait Payload: Sized {
+    /// Name of the payload's top-level element
+    const NAME: &'static str;
+    /// Namespace of the payload's top-level element
+    const NS: &'static str;
+
+    fn try_from(elem: &xml::Element) -> Result<Self, &'static str>;
+    fn to_element(&self) -> xml::Element;
+}
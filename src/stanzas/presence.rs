@@ -7,9 +7,10 @@
 use crate::ns;
 use xml;
 
+use super::lang::{best_match, get_texts, set_text, Texts};
 use super::{Stanza, StanzaType};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum PresenceType {
     Error,
     Probe,
@@ -36,6 +37,36 @@ impl StanzaType for PresenceType {
     }
 }
 
+/// Availability sub-state, as given in `<show/>`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Show {
+    Away,
+    Chat,
+    Dnd,
+    Xa,
+}
+
+impl Show {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            Show::Away => "away",
+            Show::Chat => "chat",
+            Show::Dnd => "dnd",
+            Show::Xa => "xa",
+        }
+    }
+
+    fn from_str(show: &str) -> Option<Show> {
+        match show {
+            "away" => Some(Show::Away),
+            "chat" => Some(Show::Chat),
+            "dnd" => Some(Show::Dnd),
+            "xa" => Some(Show::Xa),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Presence {
     elem: xml::Element,
@@ -79,4 +110,52 @@ impl Presence {
         };
         Presence { elem }
     }
+
+    pub fn show(&self) -> Option<Show> {
+        self.elem
+            .get_child("show", Some(ns::JABBER_CLIENT))
+            .and_then(|show| Show::from_str(show.content_str().trim()))
+    }
+
+    pub fn set_show(&mut self, show: Option<Show>) {
+        let show = show.map(|show| show.as_str().into());
+        set_text(&mut self.elem, "show", ns::JABBER_CLIENT, None, show);
+    }
+
+    /// All `<status/>` texts, keyed by language
+    pub fn statuses(&self) -> Texts {
+        get_texts(&self.elem, "status", ns::JABBER_CLIENT)
+    }
+
+    /// The `<status/>` best matching `lang`, or the presence's default language
+    pub fn status(&self, lang: Option<&str>) -> Option<String> {
+        let statuses = self.statuses();
+        let default = self.lang();
+        best_match(&statuses, lang.or(default).unwrap_or(""), default).map(|x| x.into())
+    }
+
+    /// Sets the `<status/>` for `lang`, or removes it if `status` is `None`
+    pub fn set_status(&mut self, lang: Option<&str>, status: Option<String>) {
+        set_text(&mut self.elem, "status", ns::JABBER_CLIENT, lang, status);
+    }
+
+    /// The `<priority/>` of this resource.
+    /// Missing priorities and values outside of -128..127 are treated as 0.
+    pub fn priority(&self) -> i8 {
+        self.elem
+            .get_child("priority", Some(ns::JABBER_CLIENT))
+            .and_then(|prio| prio.content_str().trim().parse().ok())
+            .unwrap_or(0)
+    }
+
+    pub fn set_priority(&mut self, priority: i8) {
+        let priority = Some(priority.to_string());
+        set_text(
+            &mut self.elem,
+            "priority",
+            ns::JABBER_CLIENT,
+            None,
+            priority,
+        );
+    }
 }
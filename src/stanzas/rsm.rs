@@ -0,0 +1,229 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+use crate::ns;
+use xml;
+
+use super::Payload;
+
+/// Result Set Management (XEP-0059)
+///
+/// Requests set `max` and `after`, `before` or `index` to select a page,
+/// responses describe the returned page with `first`, `last` and `count`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResultSet {
+    /// The maximum number of items in the page
+    pub max: Option<usize>,
+    /// Requests the page following this item id
+    pub after: Option<String>,
+    /// Requests the page preceding this item id, `Some("")` for the last page
+    pub before: Option<String>,
+    /// Requests the page starting at this position
+    pub index: Option<usize>,
+    /// The id of the first item in the page
+    pub first: Option<String>,
+    /// The position of the first item in the full result set
+    pub first_index: Option<usize>,
+    /// The id of the last item in the page
+    pub last: Option<String>,
+    /// The number of items in the full result set
+    pub count: Option<usize>,
+}
+
+impl ResultSet {
+    /// Requests the first page with at most `max` items
+    pub fn with_max(max: usize) -> ResultSet {
+        ResultSet {
+            max: Some(max),
+            ..ResultSet::default()
+        }
+    }
+}
+
+impl Payload for ResultSet {
+    const NAME: &'static str = "set";
+    const NS: &'static str = ns::RSM;
+
+    fn try_from(elem: &xml::Element) -> Result<ResultSet, &'static str> {
+        if elem.name != Self::NAME || elem.ns.as_deref() != Some(Self::NS) {
+            return Err("Not a result set");
+        }
+        let text = |name| {
+            elem.get_child(name, Some(Self::NS))
+                .map(|e| e.content_str())
+        };
+        let number = |value: Option<String>| match value {
+            Some(value) => value.parse().map(Some).map_err(|_| "Invalid RSM number"),
+            None => Ok(None),
+        };
+        let first = elem.get_child("first", Some(Self::NS));
+        Ok(ResultSet {
+            max: number(text("max"))?,
+            after: text("after"),
+            before: text("before"),
+            index: number(text("index"))?,
+            first: first.map(|e| e.content_str()),
+            first_index: number(
+                first
+                    .and_then(|e| e.get_attribute("index", None))
+                    .map(|x| x.into()),
+            )?,
+            last: text("last"),
+            count: number(text("count"))?,
+        })
+    }
+
+    fn to_element(&self) -> xml::Element {
+        let mut set = xml::Element::new(Self::NAME.into(), Some(Self::NS.into()), vec![]);
+        let numbers = [("max", self.max), ("index", self.index)];
+        for &(name, value) in numbers.iter() {
+            if let Some(value) = value {
+                set.tag(xml::Element::new(
+                    name.into(),
+                    Some(Self::NS.into()),
+                    vec![],
+                ))
+                .text(value.to_string());
+            }
+        }
+        let ids = [("after", &self.after), ("before", &self.before)];
+        for &(name, value) in ids.iter() {
+            if let Some(ref value) = *value {
+                let elem = set.tag(xml::Element::new(
+                    name.into(),
+                    Some(Self::NS.into()),
+                    vec![],
+                ));
+                if !value.is_empty() {
+                    elem.text(value.clone());
+                }
+            }
+        }
+        if let Some(ref first) = self.first {
+            let attrs = self
+                .first_index
+                .map(|index| ("index".into(), None, index.to_string()));
+            set.tag(xml::Element::new(
+                "first".into(),
+                Some(Self::NS.into()),
+                attrs,
+            ))
+            .text(first.clone());
+        }
+        if let Some(ref last) = self.last {
+            set.tag(xml::Element::new(
+                "last".into(),
+                Some(Self::NS.into()),
+                vec![],
+            ))
+            .text(last.clone());
+        }
+        if let Some(count) = self.count {
+            set.tag(xml::Element::new(
+                "count".into(),
+                Some(Self::NS.into()),
+                vec![],
+            ))
+            .text(count.to_string());
+        }
+        set
+    }
+}
+
+/// Walks through the pages of a result set
+///
+/// Call `first()` for the set of the initial request, then `next()` with each
+/// response until it returns `None`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pager {
+    page_size: Option<usize>,
+    limit: Option<usize>,
+    backwards: bool,
+    received: usize,
+}
+
+impl Pager {
+    /// Fetches pages of at most `page_size` items, or of the size the
+    /// responder chooses if `None`, until the result set is exhausted
+    pub fn new(page_size: Option<usize>) -> Pager {
+        Pager {
+            page_size,
+            limit: None,
+            backwards: false,
+            received: 0,
+        }
+    }
+
+    /// Stops once `limit` items were received
+    pub fn with_limit(mut self, limit: usize) -> Pager {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Starts at the end of the result set, e.g. with the most recent messages
+    pub fn backwards(mut self) -> Pager {
+        self.backwards = true;
+        self
+    }
+
+    /// The number of items received so far
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    /// The set requesting the first page
+    pub fn first(&self) -> ResultSet {
+        ResultSet {
+            max: self.max(),
+            before: if self.backwards {
+                Some(String::new())
+            } else {
+                None
+            },
+            ..ResultSet::default()
+        }
+    }
+
+    /// Accounts for a page of `items` items described by `response`.
+    ///
+    /// Returns the set requesting the next page, or `None` if there is none.
+    pub fn next(&mut self, response: Option<&ResultSet>, items: usize) -> Option<ResultSet> {
+        self.received += items;
+        let response = response?;
+        if items == 0 || self.limit.is_some_and(|limit| self.received >= limit) {
+            return None;
+        }
+        let mut set = ResultSet {
+            max: self.max(),
+            ..ResultSet::default()
+        };
+        if self.backwards {
+            if response.first_index == Some(0) {
+                return None;
+            }
+            set.before = Some(response.first.clone()?);
+        } else {
+            let end = response.first_index.map(|index| index + items);
+            if end
+                .zip(response.count)
+                .is_some_and(|(end, count)| end >= count)
+            {
+                return None;
+            }
+            set.after = Some(response.last.clone()?);
+        }
+        Some(set)
+    }
+
+    /// The page size, reduced to the number of items still allowed by the limit
+    fn max(&self) -> Option<usize> {
+        let remaining = self.limit.map(|limit| limit.saturating_sub(self.received));
+        match (self.page_size, remaining) {
+            (Some(size), Some(remaining)) => Some(size.min(remaining)),
+            (size, remaining) => size.or(remaining),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+// rust-xmpp
+// Copyright (c) 2015 Florian Zeitz
+//
+// This project is MIT licensed.
+// Please see the COPYING file for more information.
+
+//! Presence subscription management (RFC 6121, section 3)
+
+use std::io;
+
+use crate::jid;
+use crate::stanzas::{Presence, PresenceType, Stanza};
+use crate::{Event, XmppHandler, XmppStream};
+
+impl XmppStream {
+    /// Asks `jid` for permission to see its presence
+    pub fn request_subscription(&mut self, jid: &str) -> io::Result<()> {
+        self.handler.send_subscription(jid, PresenceType::Subscribe)
+    }
+
+    /// Allows `jid` to see our presence, answering its subscription request
+    pub fn approve_subscription(&mut self, jid: &str) -> io::Result<()> {
+        self.handler.subscription_requests.remove(jid::bare(jid));
+        self.handler
+            .send_subscription(jid, PresenceType::Subscribed)
+    }
+
+    /// Allows `jid` to see our presence before it asked for it.
+    ///
+    /// Fails with `ErrorKind::Unsupported` if the server does not support
+    /// subscription pre-approval.
+    pub fn preapprove_subscription(&mut self, jid: &str) -> io::Result<()> {
+        let supported = self
+            .handler
+            .features
+            .as_ref()
+            .is_some_and(|f| f.pre_approval);
+        if !supported {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Server does not support subscription pre-approval",
+            ));
+        }
+        self.handler
+            .send_subscription(jid, PresenceType::Subscribed)
+    }
+
+    /// Refuses the subscription request of `jid`
+    pub fn deny_subscription(&mut self, jid: &str) -> io::Result<()> {
+        self.handler.subscription_requests.remove(jid::bare(jid));
+        self.handler
+            .send_subscription(jid, PresenceType::Unsubscribed)
+    }
+
+    /// Revokes a previously approved subscription of `jid` to our presence
+    pub fn cancel_subscription(&mut self, jid: &str) -> io::Result<()> {
+        self.handler
+            .send_subscription(jid, PresenceType::Unsubscribed)
+    }
+
+    /// Stops our subscription to the presence of `jid`
+    pub fn unsubscribe(&mut self, jid: &str) -> io::Result<()> {
+        self.handler
+            .send_subscription(jid, PresenceType::Unsubscribe)
+    }
+
+    /// Bare JIDs of contacts whose subscription requests are still unanswered
+    pub fn subscription_requests(&self) -> impl Iterator<Item = &str> {
+        self.handler.subscription_requests.iter().map(|x| &x[..])
+    }
+}
+
+impl XmppHandler {
+    fn send_subscription(&mut self, jid: &str, ty: PresenceType) -> io::Result<()> {
+        let mut presence = Presence::new(ty, self.next_id());
+        presence.set_to(Some(jid::bare(jid).into()));
+        self.send(presence)
+    }
+
+    /// Turns subscription related presences into events, returns all others
+    pub(crate) fn handle_subscription(&mut self, presence: Presence) -> Option<Presence> {
+        let from = match presence.from() {
+            Some(from) => jid::bare(from).to_string(),
+            None => return Some(presence),
+        };
+        let event = match presence.stanza_type() {
+            Some(PresenceType::Subscribe) => {
+                self.subscription_requests.insert(from);
+                Event::SubscriptionRequest(presence)
+            }
+            Some(PresenceType::Subscribed) => Event::SubscriptionApproved(from),
+            Some(PresenceType::Unsubscribed) => {
+                self.subscription_requests.remove(&from);
+                Event::SubscriptionDenied(from)
+            }
+            Some(PresenceType::Unsubscribe) => Event::ContactUnsubscribed(from),
+            _ => return Some(presence),
+        };
+        self.events.push_back(event);
+        None
+    }
+}
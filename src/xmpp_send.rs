@@ -12,12 +12,38 @@ pub trait XmppSend: fmt::Display {
     fn xmpp_send<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
         write!(w, "{}", self)
     }
+
+    /// Allows adding our capabilities to outgoing presence
+    fn as_presence(&self) -> Option<&stanzas::Presence> {
+        None
+    }
+
+    /// Allows tracking outgoing messages requesting a receipt
+    fn as_message(&self) -> Option<&stanzas::Message> {
+        None
+    }
 }
 
-impl<'a, T: XmppSend> XmppSend for &'a T {}
+impl<T: XmppSend> XmppSend for &T {
+    fn as_presence(&self) -> Option<&stanzas::Presence> {
+        (**self).as_presence()
+    }
+
+    fn as_message(&self) -> Option<&stanzas::Message> {
+        (**self).as_message()
+    }
+}
 
 impl XmppSend for xml::Element {}
 
 impl XmppSend for stanzas::Iq {}
-impl XmppSend for stanzas::Message {}
-impl XmppSend for stanzas::Presence {}
+impl XmppSend for stanzas::Message {
+    fn as_message(&self) -> Option<&stanzas::Message> {
+        Some(self)
+    }
+}
+impl XmppSend for stanzas::Presence {
+    fn as_presence(&self) -> Option<&stanzas::Presence> {
+        Some(self)
+    }
+}
@@ -6,15 +6,16 @@
 
 use openssl::ssl::{SslConnector, SslMethod, SslStream};
 use std::io;
-use std::io::{BufReader, Write};
+use std::io::Write;
 use std::mem;
-use std::net::TcpStream;
+use std::net::{Shutdown, TcpStream};
+use std::time::Duration;
 
-use crate::read_str::ReadString;
+use crate::read_str::{ReadString, StrReader};
 
 pub enum XmppSocket {
-    Tcp(BufReader<TcpStream>, TcpStream),
-    Tls(BufReader<SslStream<TcpStream>>),
+    Tcp(StrReader<TcpStream>, TcpStream),
+    Tls(StrReader<SslStream<TcpStream>>),
     NoSock,
 }
 
@@ -24,28 +25,53 @@ impl XmppSocket {
         if let XmppSocket::Tcp(_, sock) = socket {
             let ctx = match SslConnector::builder(SslMethod::tls()) {
                 Ok(builder) => builder.build(),
-                Err(_) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        "Could not create SSL context",
-                    ))
-                }
+                Err(_) => return Err(io::Error::other("Could not create SSL context")),
             };
+            // A keepalive read timeout would break off the handshake
+            let timeout = sock.read_timeout()?;
+            sock.set_read_timeout(None)?;
             let ssl = match ctx.connect(domain, sock) {
                 Ok(ssl) => ssl,
-                Err(_) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        "Could not create SSL stream",
-                    ))
-                }
+                Err(_) => return Err(io::Error::other("Could not create SSL stream")),
             };
-            *self = XmppSocket::Tls(BufReader::new(ssl));
+            ssl.get_ref().set_read_timeout(timeout)?;
+            *self = XmppSocket::Tls(StrReader::new(ssl));
         } else {
             panic!("No socket, or TLS already negotiated");
         }
         Ok(())
     }
+
+    pub fn is_tls(&self) -> bool {
+        matches!(*self, XmppSocket::Tls(_))
+    }
+
+    /// Closes the connection, sending a TLS close_notify if applicable
+    pub fn shutdown(&mut self) {
+        match mem::replace(self, XmppSocket::NoSock) {
+            XmppSocket::Tcp(_, stream) => {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            XmppSocket::Tls(mut stream) => {
+                let _ = stream.get_mut().shutdown();
+                let _ = stream.get_ref().get_ref().shutdown(Shutdown::Both);
+            }
+            XmppSocket::NoSock => (),
+        }
+    }
+
+    /// Limits how long `read_str` blocks, `None` blocks indefinitely
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match *self {
+            XmppSocket::Tcp(_, ref stream) => stream.set_read_timeout(timeout),
+            XmppSocket::Tls(ref stream) => stream.get_ref().get_ref().set_read_timeout(timeout),
+            XmppSocket::NoSock => Ok(()),
+        }
+    }
+}
+
+fn not_connected() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "No socket")
 }
 
 impl Write for XmppSocket {
@@ -53,7 +79,7 @@ impl Write for XmppSocket {
         match *self {
             XmppSocket::Tcp(_, ref mut stream) => stream.write(buf),
             XmppSocket::Tls(ref mut stream) => stream.get_mut().write(buf),
-            XmppSocket::NoSock => panic!("No socket yet"),
+            XmppSocket::NoSock => Err(not_connected()),
         }
     }
 
@@ -61,7 +87,7 @@ impl Write for XmppSocket {
         match *self {
             XmppSocket::Tcp(_, ref mut stream) => stream.flush(),
             XmppSocket::Tls(ref mut stream) => stream.get_mut().flush(),
-            XmppSocket::NoSock => panic!("No socket yet"),
+            XmppSocket::NoSock => Err(not_connected()),
         }
     }
 }
@@ -71,7 +97,7 @@ impl ReadString for XmppSocket {
         match *self {
             XmppSocket::Tcp(ref mut stream, _) => stream.read_str(),
             XmppSocket::Tls(ref mut stream) => stream.read_str(),
-            XmppSocket::NoSock => panic!("Tried to read string before socket exists"),
+            XmppSocket::NoSock => Err(not_connected()),
         }
     }
 }